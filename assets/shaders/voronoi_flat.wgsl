@@ -1,6 +1,34 @@
 // voronoi_flat.wgsl
 
+#import bevy_pbr::mesh_functions::{get_world_from_local, mesh_position_local_to_clip}
+
+// one colour per cell, indexed by the cell attribute of the mesh.
+@group(2) @binding(0) var<storage, read> cell_colors: array<vec4<f32>>;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) cell: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // flat interpolation uses the first vertex of the triangle, which is always the cell site.
+    @location(0) @interpolate(flat) cell: u32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(
+        get_world_from_local(vertex.instance_index),
+        vec4<f32>(vertex.position, 1.0),
+    );
+    out.cell = vertex.cell;
+    return out;
+}
+
 @fragment
-fn fs_main() -> @location(0) vec4<f32> {
-    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
-}
\ No newline at end of file
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return cell_colors[in.cell];
+}
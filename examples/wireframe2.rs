@@ -50,7 +50,7 @@ impl VoronoiResource {
 }
 
 fn setup(mut commands: Commands, mut gizmo_group: ResMut<GizmoConfigStore>) {
-    let (gizmo_config, _) = gizmo_group.config_mut::<MeshGizmos>();
+    let (_gizmo_config, _) = gizmo_group.config_mut::<MeshGizmos>();
     //gizmo_config.line_style = GizmoLineStyle::Dotted;
     //gizmo_config.line_width = 1.0;
     //gizmo_config.line_perspective = true;
@@ -141,7 +141,7 @@ fn draw_cells(gizmos: &mut Gizmos, voronoi: &Voronoi) {
     });
 }
 fn draw_mesh(gizmos: &mut Gizmos<MeshGizmos>, voronoi: &Voronoi) {
-    let (vertices, indicies) = voronoi.mesh_buffers();
+    let (vertices, indicies, _) = voronoi.mesh_buffers();
 
     // lets debug the mesh buffers!
     for i in indicies.chunks(3) {
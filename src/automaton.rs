@@ -0,0 +1,115 @@
+use rayon::prelude::*;
+
+use crate::{
+    layer::{ElementId, Layer},
+    voronoi::Voronoi,
+};
+
+/// A cellular automaton on the irregular graph of a voronoi, e.g. forest growth, plague or fire spread.
+///
+/// Every step computes the new state of all elements from the previous states, so the order of the elements does not
+/// matter. Per-element data the rule needs, like the terrain, can be part of the state.
+#[derive(Clone, Debug)]
+pub struct Automaton<I, T> {
+    neighbors: Layer<I, Vec<I>>,
+    current: Layer<I, T>,
+    next: Layer<I, T>,
+    generation: usize,
+    /// Run the rule on all cores, the rule must then not depend on the order it is called in.
+    pub parallel: bool,
+}
+
+/// The states of the neighbours of an element.
+#[derive(Clone)]
+pub struct Neighbors<'a, I, T> {
+    ids: std::slice::Iter<'a, I>,
+    states: &'a Layer<I, T>,
+}
+
+impl<'a, I: ElementId, T> Iterator for Neighbors<'a, I, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ids.next().map(|id| &self.states[*id])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+impl<I: ElementId, T> ExactSizeIterator for Neighbors<'_, I, T> {}
+
+impl<I: ElementId + Send + Sync, T: Clone + Send + Sync> Automaton<I, T> {
+    pub fn new(voronoi: &Voronoi, initial: Layer<I, T>) -> Self {
+        Self {
+            neighbors: I::neighbor_table(voronoi),
+            next: initial.clone(),
+            current: initial,
+            generation: 0,
+            parallel: false,
+        }
+    }
+
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn state(&self) -> &Layer<I, T> {
+        &self.current
+    }
+
+    pub fn into_state(self) -> Layer<I, T> {
+        self.current
+    }
+
+    /// The number of steps run so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Replace the state of every element with `rule` applied to its state and the states of its neighbours.
+    pub fn step(&mut self, rule: impl Fn(&T, Neighbors<'_, I, T>) -> T + Sync) {
+        let current = &self.current;
+        let neighbors = &self.neighbors;
+        let update = |(index, next): (usize, &mut T)| {
+            let id = I::from_index(index);
+            *next = rule(
+                &current[id],
+                Neighbors {
+                    ids: neighbors[id].iter(),
+                    states: current,
+                },
+            );
+        };
+        if self.parallel {
+            self.next
+                .values_mut()
+                .par_iter_mut()
+                .enumerate()
+                .for_each(update);
+        } else {
+            self.next
+                .values_mut()
+                .iter_mut()
+                .enumerate()
+                .for_each(update);
+        }
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.generation += 1;
+    }
+
+    /// Run `steps` steps, `on_step` is called with the generation and the state after every step.
+    pub fn run(
+        &mut self,
+        steps: usize,
+        rule: impl Fn(&T, Neighbors<'_, I, T>) -> T + Sync,
+        mut on_step: impl FnMut(usize, &Layer<I, T>),
+    ) {
+        for _ in 0..steps {
+            self.step(&rule);
+            on_step(self.generation, &self.current);
+        }
+    }
+}
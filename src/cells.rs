@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+use crate::{
+    map::VoronoiMap,
+    render::{CellColors, MapRenderSet, DEFAULT_CELL_COLOR},
+    voronoi::CellId,
+};
+
+/// Optional mode that spawns one entity per cell of the [`VoronoiMap`], so gameplay can attach components to cells.
+///
+/// Each entity gets a [`MapCell`] and a [`CellColor`], changes to [`CellColor`] are written to [`CellColors`].
+/// Entities are respawned whenever the map changes. Requires [`crate::render::MapRenderPlugin`].
+pub struct CellEntitiesPlugin;
+
+impl Plugin for CellEntitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CellEntities>().add_systems(
+            Update,
+            (
+                spawn_cell_entities.run_if(resource_exists_and_changed::<VoronoiMap>),
+                sync_cell_colors,
+            )
+                .chain()
+                .after(MapRenderSet::Mesh)
+                .before(MapRenderSet::Upload),
+        );
+    }
+}
+
+/// The cell an entity stands for.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MapCell(pub CellId);
+
+/// The colour the cell is rendered with.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CellColor(pub Color);
+
+impl Default for CellColor {
+    fn default() -> Self {
+        Self(DEFAULT_CELL_COLOR)
+    }
+}
+
+/// Lookup from [`CellId`] to the entity spawned for that cell.
+#[derive(Resource, Default, Debug)]
+pub struct CellEntities {
+    entities: Vec<Entity>,
+}
+
+impl CellEntities {
+    pub fn get(&self, cell: CellId) -> Option<Entity> {
+        self.entities.get(cell.0).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (CellId, Entity)> + '_ {
+        self.entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (CellId(i), *e))
+    }
+}
+
+fn spawn_cell_entities(
+    mut commands: Commands,
+    map: Res<VoronoiMap>,
+    mut cell_entities: ResMut<CellEntities>,
+) {
+    for entity in cell_entities.entities.drain(..) {
+        if let Some(entity) = commands.get_entity(entity) {
+            entity.despawn_recursive();
+        }
+    }
+    cell_entities.entities = map
+        .voronoi()
+        .cell_ids()
+        .map(|cell| commands.spawn((MapCell(cell), CellColor::default())).id())
+        .collect();
+}
+
+fn sync_cell_colors(
+    changed: Query<(&MapCell, &CellColor), Changed<CellColor>>,
+    mut colors: ResMut<CellColors>,
+) {
+    changed
+        .iter()
+        .for_each(|(cell, color)| colors.set(cell.0, color.0));
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use bevy::color::{Color, HexColorError, Srgba};
+use serde::Deserialize;
+
+use crate::{
+    distance::{self, Metric},
+    layer::CellLayer,
+    rivers::Rivers,
+    terrain::Elevation,
+    voronoi::Voronoi,
+    water::Surface,
+};
+
+#[derive(Clone, Debug)]
+pub struct ClimateSettings {
+    /// The latitude in degrees at the bottom edge of the boundary.
+    pub south_latitude: f32,
+    /// The latitude in degrees at the top edge of the boundary.
+    pub north_latitude: f32,
+    pub sea_level: f32,
+    /// The temperature lost per unit of elevation above sea level.
+    pub lapse_rate: f32,
+    /// The fraction of moisture kept with every cell away from water, in `[0, 1]`.
+    pub moisture_decay: f32,
+    /// The moisture next to a river relative to the moisture next to open water.
+    pub river_moisture: f32,
+}
+
+impl Default for ClimateSettings {
+    fn default() -> Self {
+        Self {
+            south_latitude: -90.0,
+            north_latitude: 90.0,
+            sea_level: 0.2,
+            lapse_rate: 1.0,
+            moisture_decay: 0.8,
+            river_moisture: 0.8,
+        }
+    }
+}
+
+/// Temperature and moisture of every cell, both in `[0, 1]`.
+///
+/// The temperature at sea level falls linearly from 1 at the equator to 0 at the poles.
+#[derive(Clone, Debug, Default)]
+pub struct Climate {
+    pub temperature: CellLayer<f32>,
+    pub moisture: CellLayer<f32>,
+}
+
+impl Climate {
+    pub fn generate(
+        voronoi: &Voronoi,
+        elevation: &Elevation,
+        surface: &CellLayer<Surface>,
+        rivers: Option<&Rivers>,
+        settings: &ClimateSettings,
+    ) -> Self {
+        let bbox = voronoi.inner().bounding_box();
+        let bottom = (bbox.center().y - bbox.height() / 2.0) as f32;
+        let height = bbox.height() as f32;
+        let temperature = CellLayer::from_fn(voronoi, |cell| {
+            let [_, y] = voronoi.cell_position(cell);
+            let t = ((y - bottom) / height).clamp(0.0, 1.0);
+            let latitude =
+                settings.south_latitude + t * (settings.north_latitude - settings.south_latitude);
+            let above_sea = (elevation.cells[cell] - settings.sea_level).max(0.0);
+            (1.0 - latitude.abs() / 90.0 - settings.lapse_rate * above_sea).clamp(0.0, 1.0)
+        });
+
+        let water = distance::from_matching(voronoi, |cell| surface[cell].is_water(), Metric::Hops);
+        let river = match rivers {
+            Some(rivers) => distance::from_rivers(voronoi, rivers, Metric::Hops),
+            None => CellLayer::new(voronoi, f32::INFINITY),
+        };
+        let moisture = CellLayer::from_fn(voronoi, |cell| {
+            let from = |hops: f32| match hops.is_finite() {
+                true => settings.moisture_decay.powi(hops as i32),
+                false => 0.0,
+            };
+            from(water[cell])
+                .max(settings.river_moisture * from(river[cell]))
+                .clamp(0.0, 1.0)
+        });
+
+        Self {
+            temperature,
+            moisture,
+        }
+    }
+
+    /// Blend rainfall (e.g. from [`Wind`](crate::wind::Wind)) into the moisture, `weight` in `[0, 1]` is the share of the rainfall.
+    ///
+    /// The rainfall is normalized by its maximum, so rain shadows become dry even next to water.
+    pub fn apply_rainfall(&mut self, rainfall: &CellLayer<f32>, weight: f32) {
+        let max = rainfall.values().iter().copied().fold(0.0, f32::max);
+        if max <= 0.0 {
+            return;
+        }
+        self.moisture
+            .values_mut()
+            .iter_mut()
+            .zip(rainfall.values())
+            .for_each(|(moisture, rain)| {
+                *moisture = ((1.0 - weight) * *moisture + weight * rain / max).clamp(0.0, 1.0)
+            });
+    }
+}
+
+/// An index into the biomes of a [`BiomeTable`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct BiomeId(pub usize);
+
+#[derive(Clone, Debug)]
+pub struct Biome {
+    pub name: String,
+    pub color: Color,
+    /// How well the biome supports farming, in `[0, 1]`.
+    pub fertility: f32,
+}
+
+/// A Whittaker-style lookup from temperature and moisture to biome, loaded from ron, see `assets/biomes.ron`.
+#[derive(Clone, Debug)]
+pub struct BiomeTable {
+    biomes: Vec<Biome>,
+    ocean: BiomeId,
+    lake: BiomeId,
+    /// Temperature bands from coldest to hottest, each with its biomes from driest to wettest.
+    zones: Vec<Vec<BiomeId>>,
+}
+
+#[derive(Deserialize)]
+struct BiomeTableData {
+    biomes: Vec<BiomeData>,
+    ocean: String,
+    lake: String,
+    zones: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct BiomeData {
+    name: String,
+    /// An srgb hex colour, e.g. `"#44447a"`.
+    color: String,
+    #[serde(default)]
+    fertility: f32,
+}
+
+#[derive(Debug)]
+pub enum BiomeTableError {
+    Ron(ron::error::SpannedError),
+    Color { biome: String, error: HexColorError },
+    UnknownBiome(String),
+    EmptyZone,
+}
+
+impl fmt::Display for BiomeTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BiomeTableError::Ron(error) => write!(f, "invalid biome table: {error}"),
+            BiomeTableError::Color { biome, error } => {
+                write!(f, "invalid colour for biome {biome:?}: {error}")
+            }
+            BiomeTableError::UnknownBiome(name) => write!(f, "unknown biome {name:?}"),
+            BiomeTableError::EmptyZone => write!(f, "biome table has an empty zone"),
+        }
+    }
+}
+
+impl std::error::Error for BiomeTableError {}
+
+impl Default for BiomeTable {
+    fn default() -> Self {
+        Self::from_ron(include_str!("../assets/biomes.ron"))
+            .expect("the default biome table is valid")
+    }
+}
+
+impl BiomeTable {
+    pub fn from_ron(source: &str) -> Result<Self, BiomeTableError> {
+        let data: BiomeTableData = ron::from_str(source).map_err(BiomeTableError::Ron)?;
+        let biomes = data
+            .biomes
+            .into_iter()
+            .map(|biome| {
+                let color = Srgba::hex(&biome.color).map_err(|error| BiomeTableError::Color {
+                    biome: biome.name.clone(),
+                    error,
+                })?;
+                Ok(Biome {
+                    name: biome.name,
+                    color: color.into(),
+                    fertility: biome.fertility.clamp(0.0, 1.0),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let find = |name: &str| {
+            biomes
+                .iter()
+                .position(|biome| biome.name == name)
+                .map(BiomeId)
+                .ok_or_else(|| BiomeTableError::UnknownBiome(name.to_string()))
+        };
+        let ocean = find(&data.ocean)?;
+        let lake = find(&data.lake)?;
+        let zones = data
+            .zones
+            .iter()
+            .map(|zone| match zone.is_empty() {
+                true => Err(BiomeTableError::EmptyZone),
+                false => zone.iter().map(|name| find(name)).collect(),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if zones.is_empty() {
+            return Err(BiomeTableError::EmptyZone);
+        }
+        Ok(Self {
+            biomes,
+            ocean,
+            lake,
+            zones,
+        })
+    }
+
+    pub fn biome(&self, id: BiomeId) -> &Biome {
+        &self.biomes[id.0]
+    }
+
+    pub fn biomes(&self) -> impl Iterator<Item = (BiomeId, &Biome)> {
+        self.biomes.iter().enumerate().map(|(i, b)| (BiomeId(i), b))
+    }
+
+    pub fn color(&self, id: BiomeId) -> Color {
+        self.biome(id).color
+    }
+
+    pub fn fertility(&self, id: BiomeId) -> f32 {
+        self.biome(id).fertility
+    }
+
+    /// The land biome for a temperature and moisture in `[0, 1]`.
+    pub fn lookup(&self, temperature: f32, moisture: f32) -> BiomeId {
+        let band = |value: f32, count: usize| {
+            ((value.clamp(0.0, 1.0) * count as f32) as usize).min(count - 1)
+        };
+        let zone = &self.zones[band(temperature, self.zones.len())];
+        zone[band(moisture, zone.len())]
+    }
+
+    /// The biome of every cell, water cells are ocean or lake.
+    pub fn classify(&self, surface: &CellLayer<Surface>, climate: &Climate) -> CellLayer<BiomeId> {
+        CellLayer::from_vec(
+            surface
+                .values()
+                .iter()
+                .zip(climate.temperature.values())
+                .zip(climate.moisture.values())
+                .map(|((surface, temperature), moisture)| match surface {
+                    Surface::Ocean => self.ocean,
+                    Surface::Lake => self.lake,
+                    Surface::Land => self.lookup(*temperature, *moisture),
+                })
+                .collect(),
+        )
+    }
+}
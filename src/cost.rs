@@ -0,0 +1,100 @@
+use std::collections::HashSet;
+
+use crate::{
+    layer::CellLayer,
+    rivers::Rivers,
+    terrain::Elevation,
+    voronoi::{CellId, Voronoi},
+    water::Surface,
+};
+
+/// How much each kind of terrain adds to the cost of moving between two neighbouring cells.
+#[derive(Clone, Debug)]
+pub struct CostWeights {
+    /// The cost per unit of distance between the sites.
+    pub distance: f32,
+    /// The extra cost per unit of elevation climbed, relative to the distance.
+    pub climb: f32,
+    /// The extra cost for the elevation of the cell entered, relative to the distance, so mountains are slow even when flat.
+    pub elevation: f32,
+    /// The extra cost for crossing a river.
+    pub river_crossing: f32,
+    /// The cost multiplier for entering a water cell, `None` if water cannot be entered.
+    pub water: Option<f32>,
+}
+
+impl Default for CostWeights {
+    fn default() -> Self {
+        Self {
+            distance: 1.0,
+            climb: 20.0,
+            elevation: 2.0,
+            river_crossing: 5.0,
+            water: None,
+        }
+    }
+}
+
+/// The cost of moving between neighbouring cells from elevation, water and rivers, for region growing and paths.
+#[derive(Clone, Debug, Default)]
+pub struct TerrainCost {
+    elevation: CellLayer<f32>,
+    water: CellLayer<bool>,
+    /// Pairs of neighbouring cells, lower id first, whose shared edge is a river.
+    river_crossings: HashSet<(CellId, CellId)>,
+    pub weights: CostWeights,
+}
+
+impl TerrainCost {
+    pub fn new(
+        voronoi: &Voronoi,
+        elevation: &Elevation,
+        surface: &CellLayer<Surface>,
+        rivers: Option<&Rivers>,
+        weights: CostWeights,
+    ) -> Self {
+        let mut river_crossings = HashSet::new();
+        if let Some(rivers) = rivers {
+            let corner_cells = voronoi.corner_cells();
+            for (a, b) in rivers.rivers.iter().flat_map(|river| river.edges()) {
+                let cells: Vec<CellId> = corner_cells[a]
+                    .iter()
+                    .copied()
+                    .filter(|cell| corner_cells[b].contains(cell))
+                    .collect();
+                if let [first, second] = cells[..] {
+                    river_crossings.insert((first.min(second), first.max(second)));
+                }
+            }
+        }
+        Self {
+            elevation: elevation.cells.clone(),
+            water: surface.map(Surface::is_water),
+            river_crossings,
+            weights,
+        }
+    }
+
+    /// Whether the edge between the neighbouring cells `a` and `b` is a river.
+    pub fn crosses_river(&self, a: CellId, b: CellId) -> bool {
+        self.river_crossings.contains(&(a.min(b), a.max(b)))
+    }
+
+    /// The cost of moving from `from` to its neighbour `to`, `None` if `to` cannot be entered.
+    pub fn cost(&self, voronoi: &Voronoi, from: CellId, to: CellId) -> Option<f32> {
+        let weights = &self.weights;
+        let [ax, ay] = voronoi.cell_position(from);
+        let [bx, by] = voronoi.cell_position(to);
+        let distance = (bx - ax).hypot(by - ay);
+        let climb = (self.elevation[to] - self.elevation[from]).max(0.0);
+        let mut cost = distance
+            * (weights.distance + weights.elevation * self.elevation[to] + weights.climb * climb);
+        if self.water[to] {
+            cost *= weights.water?;
+        }
+        if self.crosses_river(from, to) {
+            cost += weights.river_crossing;
+        }
+        Some(cost)
+    }
+}
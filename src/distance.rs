@@ -0,0 +1,83 @@
+use crate::{
+    layer::CellLayer,
+    pathfinding::distance_field,
+    rivers::Rivers,
+    voronoi::{CellId, Voronoi},
+    water::Surface,
+};
+
+/// How the distance between neighbouring cells is measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum Metric {
+    /// Every step between neighbours counts 1.
+    #[default]
+    Hops,
+    /// The straight line distance between the sites of neighbours, summed along the graph.
+    Euclidean,
+}
+
+impl Metric {
+    pub fn step(self, voronoi: &Voronoi, from: CellId, to: CellId) -> f32 {
+        match self {
+            Metric::Hops => 1.0,
+            Metric::Euclidean => {
+                let [ax, ay] = voronoi.cell_position(from);
+                let [bx, by] = voronoi.cell_position(to);
+                (bx - ax).hypot(by - ay)
+            }
+        }
+    }
+}
+
+/// The distance from every cell to the nearest of `cells`, `f32::INFINITY` if there are none.
+pub fn from_cells(
+    voronoi: &Voronoi,
+    cells: impl IntoIterator<Item = CellId>,
+    metric: Metric,
+) -> CellLayer<f32> {
+    distance_field(voronoi, cells, |from, to| {
+        Some(metric.step(voronoi, from, to))
+    })
+}
+
+/// The distance from every cell to the nearest cell for which `source` is true.
+pub fn from_matching(
+    voronoi: &Voronoi,
+    source: impl Fn(CellId) -> bool,
+    metric: Metric,
+) -> CellLayer<f32> {
+    from_cells(
+        voronoi,
+        voronoi.cell_ids().filter(|cell| source(*cell)),
+        metric,
+    )
+}
+
+/// The distance from the coast on land and at sea, the land and ocean cells on either side of it are 0.
+///
+/// Lake shores are not coast, use [`from_matching`] with [`Surface::is_water`] to include them.
+pub fn from_coast(
+    voronoi: &Voronoi,
+    surface: &CellLayer<Surface>,
+    metric: Metric,
+) -> CellLayer<f32> {
+    from_matching(
+        voronoi,
+        |cell| {
+            let across = match surface[cell] {
+                Surface::Ocean => Surface::Land,
+                Surface::Land => Surface::Ocean,
+                Surface::Lake => return false,
+            };
+            voronoi
+                .cell_neighbors(cell)
+                .any(|neighbor| surface[neighbor] == across)
+        },
+        metric,
+    )
+}
+
+/// The distance from the nearest cell along a river, see [`Rivers::is_riverside`].
+pub fn from_rivers(voronoi: &Voronoi, rivers: &Rivers, metric: Metric) -> CellLayer<f32> {
+    from_matching(voronoi, |cell| rivers.is_riverside(cell), metric)
+}
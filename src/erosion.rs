@@ -0,0 +1,207 @@
+use crate::{
+    layer::{ElementId, Layer},
+    voronoi::Voronoi,
+};
+
+/// Water running downhill carves valleys along the drainage and drops the sediment where the slope flattens.
+///
+/// Works on the cell or corner elevation, there is no randomness so the same parameters always give the same result.
+#[derive(Clone, Debug)]
+pub struct HydraulicErosion {
+    pub iterations: usize,
+    /// The water added to every element each iteration.
+    pub rainfall: f32,
+    /// The sediment the water can carry per unit of flow and slope.
+    pub capacity: f32,
+    /// The fraction of the missing capacity picked up as sediment.
+    pub erosion_rate: f32,
+    /// The fraction of the sediment above capacity that is dropped.
+    pub deposition_rate: f32,
+    /// Elements at or below this level (e.g. the sea level) are not eroded and swallow the sediment that reaches them.
+    pub base_level: f32,
+}
+
+impl Default for HydraulicErosion {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            rainfall: 1.0,
+            capacity: 0.01,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            base_level: 0.0,
+        }
+    }
+}
+
+impl HydraulicErosion {
+    /// Erode `elevation` in place, `on_iteration` is called with the iteration index and the elevation after every iteration.
+    pub fn run<I: ElementId>(
+        &self,
+        voronoi: &Voronoi,
+        elevation: &mut Layer<I, f32>,
+        mut on_iteration: impl FnMut(usize, &Layer<I, f32>),
+    ) {
+        let graph = Graph::new::<I>(voronoi);
+        for iteration in 0..self.iterations {
+            self.step(&graph, elevation);
+            on_iteration(iteration, elevation);
+        }
+    }
+
+    fn step<I: ElementId>(&self, graph: &Graph, elevation: &mut Layer<I, f32>) {
+        // the steepest descent and its slope for every element
+        let downslope: Vec<Option<(usize, f32)>> = (0..graph.len())
+            .map(|i| {
+                graph.neighbors[i]
+                    .iter()
+                    .map(|n| {
+                        (
+                            *n,
+                            (elevation.values()[i] - elevation.values()[*n])
+                                / graph.distance(i, *n),
+                        )
+                    })
+                    .filter(|(_, slope)| *slope > 0.0)
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+            })
+            .collect();
+
+        let mut order: Vec<usize> = (0..graph.len()).collect();
+        order.sort_by(|a, b| {
+            elevation.values()[*b]
+                .total_cmp(&elevation.values()[*a])
+                .then(a.cmp(b))
+        });
+
+        let mut water = vec![self.rainfall; graph.len()];
+        let mut sediment = vec![0.0f32; graph.len()];
+        let heights = elevation.values_mut();
+        for i in order {
+            if heights[i] <= self.base_level {
+                continue;
+            }
+            let Some((down, slope)) = downslope[i] else {
+                // a sink, the water evaporates and leaves its sediment behind
+                heights[i] += sediment[i];
+                continue;
+            };
+            let capacity = self.capacity * water[i] * slope;
+            if sediment[i] < capacity {
+                // never dig below the element the water flows into
+                let limit = (heights[i] - heights[down]).max(0.0);
+                let eroded = (self.erosion_rate * (capacity - sediment[i])).min(limit);
+                heights[i] -= eroded;
+                sediment[i] += eroded;
+            } else {
+                let deposited = self.deposition_rate * (sediment[i] - capacity);
+                heights[i] += deposited;
+                sediment[i] -= deposited;
+            }
+            water[down] += water[i];
+            sediment[down] += sediment[i];
+        }
+    }
+}
+
+/// Material slumps from slopes steeper than the talus slope down to the lower neighbours.
+#[derive(Clone, Debug)]
+pub struct ThermalErosion {
+    pub iterations: usize,
+    /// The steepest stable slope as elevation difference per unit of distance.
+    pub talus: f32,
+    /// The fraction of the material above the talus slope that moves each iteration, at most 0.5.
+    pub rate: f32,
+}
+
+impl Default for ThermalErosion {
+    fn default() -> Self {
+        Self {
+            iterations: 10,
+            talus: 0.05,
+            rate: 0.25,
+        }
+    }
+}
+
+impl ThermalErosion {
+    /// Slump `elevation` in place, `on_iteration` is called with the iteration index and the elevation after every iteration.
+    pub fn run<I: ElementId>(
+        &self,
+        voronoi: &Voronoi,
+        elevation: &mut Layer<I, f32>,
+        mut on_iteration: impl FnMut(usize, &Layer<I, f32>),
+    ) {
+        let graph = Graph::new::<I>(voronoi);
+        for iteration in 0..self.iterations {
+            self.step(&graph, elevation);
+            on_iteration(iteration, elevation);
+        }
+    }
+
+    fn step<I: ElementId>(&self, graph: &Graph, elevation: &mut Layer<I, f32>) {
+        // all moves are computed from the same heights, so the order of the elements does not matter
+        let heights = elevation.values();
+        let mut delta = vec![0.0f32; graph.len()];
+        for i in 0..graph.len() {
+            let excess: Vec<(usize, f32)> = graph.neighbors[i]
+                .iter()
+                .map(|n| {
+                    (
+                        *n,
+                        heights[i] - heights[*n] - self.talus * graph.distance(i, *n),
+                    )
+                })
+                .filter(|(_, excess)| *excess > 0.0)
+                .collect();
+            let total: f32 = excess.iter().map(|(_, e)| e).sum();
+            let Some(max) = excess.iter().map(|(_, e)| *e).reduce(f32::max) else {
+                continue;
+            };
+            // move at most half of the steepest excess, shared by how much each neighbour is too low
+            let moved = self.rate.min(0.5) * max;
+            for (n, e) in excess {
+                let share = moved * e / total;
+                delta[i] -= share;
+                delta[n] += share;
+            }
+        }
+        elevation
+            .values_mut()
+            .iter_mut()
+            .zip(delta)
+            .for_each(|(h, d)| *h += d);
+    }
+}
+
+/// The adjacency and positions of the elements, by index.
+struct Graph {
+    neighbors: Vec<Vec<usize>>,
+    positions: Vec<[f32; 2]>,
+}
+
+impl Graph {
+    fn new<I: ElementId>(voronoi: &Voronoi) -> Self {
+        let neighbors = I::neighbor_table(voronoi)
+            .values()
+            .iter()
+            .map(|n| n.iter().map(|id| id.index()).collect())
+            .collect();
+        let positions = (0..I::count(voronoi))
+            .map(|i| I::from_index(i).position(voronoi))
+            .collect();
+        Self {
+            neighbors,
+            positions,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f32 {
+        let ([ax, ay], [bx, by]) = (self.positions[a], self.positions[b]);
+        (ax - bx).hypot(ay - by).max(f32::EPSILON)
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    ecs::world::Command,
+    prelude::*,
+    tasks::{block_on, poll_once, AsyncComputeTaskPool, Task},
+};
+
+use crate::{
+    map::VoronoiMap,
+    voronoi::{Voronoi, VoronoiBuilder},
+};
+
+/// Generates maps in the background on the [`AsyncComputeTaskPool`], see [`GenerateMap`].
+pub struct MapGenerationPlugin;
+
+impl Plugin for MapGenerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<MapState>()
+            .init_resource::<MapGenerationProgress>()
+            .add_event::<MapGenerated>()
+            .add_event::<MapGenerationFailed>()
+            .add_systems(
+                PreUpdate,
+                poll_map_generation.run_if(resource_exists::<MapGenerationTask>),
+            );
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MapState {
+    /// No map has been generated yet.
+    #[default]
+    Empty,
+    /// A map is being generated, the previous [`VoronoiMap`] (if any) is still in place.
+    Loading,
+    /// The generated map has been swapped in.
+    Ready,
+}
+
+/// Progress of the running generation, updated every frame while [`MapState::Loading`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct MapGenerationProgress {
+    /// The name of the stage that is currently running.
+    pub stage: String,
+    pub completed_stages: usize,
+    pub total_stages: usize,
+}
+
+impl MapGenerationProgress {
+    /// Completed fraction in `[0, 1]`.
+    pub fn fraction(&self) -> f32 {
+        if self.total_stages == 0 {
+            return 0.0;
+        }
+        self.completed_stages as f32 / self.total_stages as f32
+    }
+}
+
+/// Sent once the generated map has been swapped in.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MapGenerated;
+
+/// Sent instead of [`MapGenerated`] when the generation failed, the previous map (if any) stays in place.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MapGenerationFailed(pub MapGenerationError);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapGenerationError {
+    /// The sites of the [`VoronoiBuilder`] do not form a voronoi, see [`VoronoiBuilder::try_build`].
+    Voronoi,
+    /// A newer generation replaced this one before it finished.
+    Cancelled,
+}
+
+impl fmt::Display for MapGenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapGenerationError::Voronoi => write!(f, "the sites do not form a voronoi"),
+            MapGenerationError::Cancelled => write!(f, "the map generation was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for MapGenerationError {}
+
+type Stage = Box<dyn FnOnce(&mut GenerationContext) + Send>;
+
+/// The work to generate a map: building the voronoi followed by the downstream stages in order.
+pub struct MapGenerator {
+    builder: VoronoiBuilder,
+    stages: Vec<(String, Stage)>,
+}
+
+impl MapGenerator {
+    pub fn new(builder: VoronoiBuilder) -> Self {
+        Self {
+            builder,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Add a stage that runs after the voronoi is built and all previously added stages.
+    pub fn with_stage(
+        mut self,
+        name: impl Into<String>,
+        stage: impl FnOnce(&mut GenerationContext) + Send + 'static,
+    ) -> Self {
+        self.stages.push((name.into(), Box::new(stage)));
+        self
+    }
+
+    /// Run the generation on the current thread.
+    ///
+    /// The generation stops between stages once `cancelled` is set, a running stage is not interrupted.
+    pub fn generate(
+        self,
+        progress: &Mutex<MapGenerationProgress>,
+        cancelled: &AtomicBool,
+    ) -> Result<GenerationContext, MapGenerationError> {
+        let set_progress = |stage: &str, completed: usize| {
+            if let Ok(mut progress) = progress.lock() {
+                progress.stage = stage.to_string();
+                progress.completed_stages = completed;
+            }
+        };
+        set_progress("voronoi", 0);
+        let mut context = GenerationContext {
+            voronoi: self
+                .builder
+                .try_build()
+                .ok_or(MapGenerationError::Voronoi)?,
+            resources: HashMap::new(),
+        };
+        for (i, (name, stage)) in self.stages.into_iter().enumerate() {
+            if cancelled.load(Ordering::Relaxed) {
+                return Err(MapGenerationError::Cancelled);
+            }
+            set_progress(&name, i + 1);
+            stage(&mut context);
+        }
+        Ok(context)
+    }
+
+    fn total_stages(&self) -> usize {
+        self.stages.len() + 1
+    }
+}
+
+/// The state shared by the stages of a [`MapGenerator`].
+///
+/// Resources inserted by a stage can be read by later stages and are inserted into the world together with the [`VoronoiMap`].
+pub struct GenerationContext {
+    voronoi: Voronoi,
+    resources: HashMap<TypeId, GeneratedResource>,
+}
+
+struct GeneratedResource {
+    value: Box<dyn Any + Send + Sync>,
+    insert: fn(Box<dyn Any + Send + Sync>, &mut World),
+}
+
+impl GenerationContext {
+    pub fn voronoi(&self) -> &Voronoi {
+        &self.voronoi
+    }
+
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) {
+        self.resources.insert(
+            TypeId::of::<R>(),
+            GeneratedResource {
+                value: Box::new(resource),
+                insert: insert_generated::<R>,
+            },
+        );
+    }
+
+    pub fn resource<R: Resource>(&self) -> Option<&R> {
+        self.resources
+            .get(&TypeId::of::<R>())
+            .and_then(|r| r.value.downcast_ref())
+    }
+
+    pub fn resource_mut<R: Resource>(&mut self) -> Option<&mut R> {
+        self.resources
+            .get_mut(&TypeId::of::<R>())
+            .and_then(|r| r.value.downcast_mut())
+    }
+
+    /// Insert the map and all generated resources into the world.
+    pub fn apply(self, world: &mut World) {
+        world.insert_resource(VoronoiMap::new(self.voronoi));
+        for resource in self.resources.into_values() {
+            (resource.insert)(resource.value, world);
+        }
+    }
+}
+
+fn insert_generated<R: Resource>(value: Box<dyn Any + Send + Sync>, world: &mut World) {
+    if let Ok(value) = value.downcast::<R>() {
+        world.insert_resource(*value);
+    }
+}
+
+/// Start generating a map in the background with `commands.queue(GenerateMap(generator))`, replacing any generation that is still running.
+pub struct GenerateMap(pub MapGenerator);
+
+impl Command for GenerateMap {
+    fn apply(self, world: &mut World) {
+        let progress = Arc::new(Mutex::new(MapGenerationProgress {
+            total_stages: self.0.total_stages(),
+            ..default()
+        }));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (task_progress, task_cancelled) = (progress.clone(), cancelled.clone());
+        let generator = self.0;
+        let task = AsyncComputeTaskPool::get()
+            .spawn(async move { generator.generate(&task_progress, &task_cancelled) });
+        // replacing the task drops the previous one, which stops it after its current stage
+        world.insert_resource(MapGenerationTask {
+            task,
+            progress,
+            cancelled,
+        });
+        world
+            .resource_mut::<NextState<MapState>>()
+            .set(MapState::Loading);
+    }
+}
+
+#[derive(Resource)]
+struct MapGenerationTask {
+    task: Task<Result<GenerationContext, MapGenerationError>>,
+    progress: Arc<Mutex<MapGenerationProgress>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for MapGenerationTask {
+    fn drop(&mut self) {
+        // dropping the task only cancels it if it has not started yet, the generation itself has no await points
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn poll_map_generation(
+    mut commands: Commands,
+    mut generation: ResMut<MapGenerationTask>,
+    mut progress: ResMut<MapGenerationProgress>,
+    mut next_state: ResMut<NextState<MapState>>,
+    mut generated: EventWriter<MapGenerated>,
+    mut failed: EventWriter<MapGenerationFailed>,
+    map: Option<Res<VoronoiMap>>,
+) {
+    if let Ok(current) = generation.progress.lock() {
+        *progress = current.clone();
+    }
+    let Some(result) = block_on(poll_once(&mut generation.task)) else {
+        return;
+    };
+    commands.remove_resource::<MapGenerationTask>();
+    match result {
+        Ok(context) => {
+            progress.completed_stages = progress.total_stages;
+            commands.queue(|world: &mut World| context.apply(world));
+            next_state.set(MapState::Ready);
+            generated.send(MapGenerated);
+        }
+        Err(error) => {
+            next_state.set(match map {
+                Some(_) => MapState::Ready,
+                None => MapState::Empty,
+            });
+            failed.send(MapGenerationFailed(error));
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    voronoi::{Boundary, CellId, Point, Voronoi, VoronoiBuilder},
+};
+
+/// Nested voronois from coarse to fine, e.g. continents, kingdoms and provinces, where every cell belongs to a cell of
+/// the level above it.
+///
+/// Level 0 is the coarsest. A fine cell belongs to the coarse cell that contains its site, so fine cells along a coarse
+/// border can stick out of it. On finer levels a region is the union of its descendants, see [`VoronoiHierarchy::ancestors`].
+pub struct VoronoiHierarchy {
+    levels: Vec<Voronoi>,
+    /// `parents[i]` maps the cells of level `i + 1` to the cells of level `i`.
+    parents: Vec<CellLayer<CellId>>,
+    /// `children[i]` maps the cells of level `i` to the cells of level `i + 1`.
+    children: Vec<CellLayer<Vec<CellId>>>,
+}
+
+impl VoronoiHierarchy {
+    pub fn new(coarsest: Voronoi) -> Self {
+        Self {
+            levels: vec![coarsest],
+            parents: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Add a finer level, its cells are grouped by the cell of the current finest level that contains their site.
+    pub fn with_level(mut self, finer: Voronoi) -> Self {
+        let coarse = self.finest();
+        let mut hint = CellId(0);
+        let parents = CellLayer::from_fn(&finer, |cell| {
+            let [x, y] = finer.cell_position(cell);
+            hint = coarse
+                .cell_at_near((x, y), hint)
+                .unwrap_or_else(|| nearest_cell(coarse, [x, y]));
+            hint
+        });
+        let mut children = CellLayer::new(coarse, Vec::new());
+        for (cell, parent) in parents.iter() {
+            children[*parent].push(cell);
+        }
+        self.levels.push(finer);
+        self.parents.push(parents);
+        self.children.push(children);
+        self
+    }
+
+    /// Add a finer level with `per_cell` random sites inside every cell of the current finest level.
+    ///
+    /// The sites are seeded from the map seed of the finest level and relaxed `relaxation` times, which can move a few
+    /// sites across a coarse border. Cells that get no random site, e.g. slivers or all cells if `per_cell` is 0, keep
+    /// their own site instead, so every cell has a child.
+    pub fn with_subdivided_level(self, per_cell: usize, relaxation: usize) -> Self {
+        let coarse = self.finest();
+        let mut rng = StdRng::seed_from_u64(coarse.seed().wrapping_add(self.levels.len() as u64));
+        let mut sites: Vec<Point> = Vec::new();
+        for cell in coarse.cell_ids() {
+            let vertices = coarse.cell_vertices(cell);
+            let (min, max) = vertices.iter().fold(
+                ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+                |(min, max), [x, y]| {
+                    (
+                        [min[0].min(*x), min[1].min(*y)],
+                        [max[0].max(*x), max[1].max(*y)],
+                    )
+                },
+            );
+            let mut placed = 0;
+            // rejection sampling in the bounding box of the cell, bounded in case the cell is a sliver
+            if min[0] < max[0] && min[1] < max[1] {
+                for _ in 0..per_cell * 32 {
+                    if placed == per_cell {
+                        break;
+                    }
+                    let (x, y) = (rng.gen_range(min[0]..max[0]), rng.gen_range(min[1]..max[1]));
+                    if coarse.cell_at_near((x, y), cell) == Some(cell) {
+                        sites.push((x, y).into());
+                        placed += 1;
+                    }
+                }
+            }
+            if placed == 0 {
+                let [x, y] = coarse.cell_position(cell);
+                sites.push((x, y).into());
+            }
+        }
+        let bbox = coarse.inner().bounding_box();
+        let finer = VoronoiBuilder::default()
+            .set_seed(coarse.seed())
+            .set_boundary(Boundary::CenteredRectangle(
+                bbox.width() as f32,
+                bbox.height() as f32,
+            ))
+            .set_sites(sites)
+            .set_lloyd_relaxation_iterations(relaxation)
+            .build();
+        self.with_level(finer)
+    }
+
+    /// The number of levels.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn level(&self, level: usize) -> &Voronoi {
+        &self.levels[level]
+    }
+
+    pub fn levels(&self) -> &[Voronoi] {
+        &self.levels
+    }
+
+    pub fn finest(&self) -> &Voronoi {
+        self.levels
+            .last()
+            .expect("a hierarchy has at least one level")
+    }
+
+    /// The cell of level `level - 1` that contains `cell`, `None` on the coarsest level.
+    pub fn parent(&self, level: usize, cell: CellId) -> Option<CellId> {
+        level
+            .checked_sub(1)
+            .map(|parent_level| self.parents[parent_level][cell])
+    }
+
+    /// The parent of every cell of `level`, `None` on the coarsest level.
+    pub fn parents(&self, level: usize) -> Option<&CellLayer<CellId>> {
+        level
+            .checked_sub(1)
+            .map(|parent_level| &self.parents[parent_level])
+    }
+
+    /// The cells of level `level + 1` inside `cell`, empty on the finest level.
+    pub fn children(&self, level: usize, cell: CellId) -> &[CellId] {
+        self.children
+            .get(level)
+            .map_or(&[][..], |children| children[cell].as_slice())
+    }
+
+    /// The cell on the coarser level `ancestor_level` that `cell` of `level` belongs to.
+    pub fn ancestor(&self, level: usize, cell: CellId, ancestor_level: usize) -> CellId {
+        assert!(
+            ancestor_level <= level,
+            "the ancestor level must not be finer than the level"
+        );
+        (ancestor_level..level)
+            .rev()
+            .fold(cell, |cell, parent_level| self.parents[parent_level][cell])
+    }
+
+    /// Map every cell of `level` to its cell on `ancestor_level`, e.g. to look up the kingdom of every province.
+    pub fn ancestors(&self, level: usize, ancestor_level: usize) -> CellLayer<CellId> {
+        CellLayer::from_fn(&self.levels[level], |cell| {
+            self.ancestor(level, cell, ancestor_level)
+        })
+    }
+
+    /// The cell containing `point` on every level, from coarsest to finest.
+    pub fn cell_at<T: Into<Point> + Copy>(&self, point: T) -> Option<Vec<CellId>> {
+        self.levels
+            .iter()
+            .map(|level| level.cell_at(point))
+            .collect()
+    }
+}
+
+fn nearest_cell(voronoi: &Voronoi, [x, y]: [f32; 2]) -> CellId {
+    voronoi
+        .cell_ids()
+        .min_by(|a, b| {
+            let distance = |cell| {
+                let [cx, cy] = voronoi.cell_position(cell);
+                (cx - x).hypot(cy - y)
+            };
+            distance(*a).total_cmp(&distance(*b))
+        })
+        .unwrap_or(CellId(0))
+}
@@ -0,0 +1,229 @@
+use std::{
+    marker::PhantomData,
+    ops::{Index, IndexMut, Range},
+};
+
+use crate::voronoi::{CellId, CornerId, Voronoi};
+
+/// An index into the elements (cells or corners) of a [`Voronoi`] that a [`Layer`] stores values for.
+pub trait ElementId: Copy {
+    /// The number of elements of this kind in `voronoi`.
+    fn count(voronoi: &Voronoi) -> usize;
+    fn from_index(index: usize) -> Self;
+    fn index(self) -> usize;
+    fn position(self, voronoi: &Voronoi) -> [f32; 2];
+    /// The elements that share an edge with each element.
+    fn neighbor_table(voronoi: &Voronoi) -> Layer<Self, Vec<Self>>;
+}
+
+impl ElementId for CellId {
+    fn count(voronoi: &Voronoi) -> usize {
+        voronoi.cell_count()
+    }
+
+    fn from_index(index: usize) -> Self {
+        CellId(index)
+    }
+
+    fn index(self) -> usize {
+        self.0
+    }
+
+    fn position(self, voronoi: &Voronoi) -> [f32; 2] {
+        voronoi.cell_position(self)
+    }
+
+    fn neighbor_table(voronoi: &Voronoi) -> CellLayer<Vec<CellId>> {
+        CellLayer::from_fn(voronoi, |cell| voronoi.cell_neighbors(cell).collect())
+    }
+}
+
+impl ElementId for CornerId {
+    fn count(voronoi: &Voronoi) -> usize {
+        voronoi.corner_count()
+    }
+
+    fn from_index(index: usize) -> Self {
+        CornerId(index)
+    }
+
+    fn index(self) -> usize {
+        self.0
+    }
+
+    fn position(self, voronoi: &Voronoi) -> [f32; 2] {
+        voronoi.corner_position(self)
+    }
+
+    fn neighbor_table(voronoi: &Voronoi) -> CornerLayer<Vec<CornerId>> {
+        voronoi.corner_neighbors()
+    }
+}
+
+/// A value for every element of a [`Voronoi`], see [`CellLayer`] and [`CornerLayer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer<I, T> {
+    values: Vec<T>,
+    _id: PhantomData<I>,
+}
+
+/// A value for every cell of a [`Voronoi`], indexed by [`CellId`].
+pub type CellLayer<T> = Layer<CellId, T>;
+
+/// A value for every corner of a [`Voronoi`], indexed by [`CornerId`].
+pub type CornerLayer<T> = Layer<CornerId, T>;
+
+impl<I, T> Default for Layer<I, T> {
+    fn default() -> Self {
+        Self::from_vec(Vec::new())
+    }
+}
+
+impl<I: ElementId, T: Clone> Layer<I, T> {
+    /// A layer with every element set to `value`.
+    pub fn new(voronoi: &Voronoi, value: T) -> Self {
+        Self::from_vec(vec![value; I::count(voronoi)])
+    }
+}
+
+impl<I, T> Layer<I, T> {
+    pub fn from_vec(values: Vec<T>) -> Self {
+        Self {
+            values,
+            _id: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Layer<I, U> {
+        Layer::from_vec(self.values.iter().map(f).collect())
+    }
+}
+
+impl<I: ElementId, T> Layer<I, T> {
+    pub fn from_fn(voronoi: &Voronoi, f: impl FnMut(I) -> T) -> Self {
+        Self::from_vec((0..I::count(voronoi)).map(I::from_index).map(f).collect())
+    }
+
+    pub fn get(&self, id: I) -> Option<&T> {
+        self.values.get(id.index())
+    }
+
+    pub fn get_mut(&mut self, id: I) -> Option<&mut T> {
+        self.values.get_mut(id.index())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (I, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (I::from_index(i), v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (I, &mut T)> {
+        self.values
+            .iter_mut()
+            .enumerate()
+            .map(|(i, v)| (I::from_index(i), v))
+    }
+}
+
+impl<I: ElementId, T> Index<I> for Layer<I, T> {
+    type Output = T;
+
+    fn index(&self, id: I) -> &T {
+        &self.values[id.index()]
+    }
+}
+
+impl<I: ElementId, T> IndexMut<I> for Layer<I, T> {
+    fn index_mut(&mut self, id: I) -> &mut T {
+        &mut self.values[id.index()]
+    }
+}
+
+/// A [`CellLayer`] that records which cells were written, so consumers (e.g. the gpu upload) only need to update those.
+#[derive(Clone, Debug, Default)]
+pub struct TrackedCellLayer<T> {
+    layer: CellLayer<T>,
+    dirty: Vec<CellId>,
+    /// Set instead of growing `dirty` past the number of cells, e.g. when nothing takes the dirty cells.
+    all_dirty: bool,
+}
+
+impl<T> TrackedCellLayer<T> {
+    pub fn new(layer: CellLayer<T>) -> Self {
+        Self {
+            layer,
+            dirty: Vec::new(),
+            all_dirty: false,
+        }
+    }
+
+    pub fn layer(&self) -> &CellLayer<T> {
+        &self.layer
+    }
+
+    pub fn get(&self, cell: CellId) -> Option<&T> {
+        self.layer.get(cell)
+    }
+
+    /// Mutable access to a cell, the cell is marked dirty.
+    pub fn get_mut(&mut self, cell: CellId) -> Option<&mut T> {
+        let len = self.layer.len();
+        let value = self.layer.get_mut(cell)?;
+        if !self.all_dirty {
+            self.dirty.push(cell);
+            if self.dirty.len() > len {
+                self.dirty = Vec::new();
+                self.all_dirty = true;
+            }
+        }
+        Some(value)
+    }
+
+    pub fn set(&mut self, cell: CellId, value: T) {
+        if let Some(v) = self.get_mut(cell) {
+            *v = value;
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.all_dirty || !self.dirty.is_empty()
+    }
+
+    /// The cells written since the last call merged into sorted contiguous ranges of cell indices, clears the dirty cells.
+    ///
+    /// Once more cells were written than the layer has, the whole layer is a single range.
+    pub fn take_dirty_ranges(&mut self) -> Vec<Range<usize>> {
+        if std::mem::take(&mut self.all_dirty) {
+            return std::iter::once(0..self.layer.len()).collect();
+        }
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort_unstable();
+        dirty.dedup();
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for cell in dirty {
+            match ranges.last_mut() {
+                Some(range) if range.end == cell.0 => range.end += 1,
+                _ => ranges.push(cell.0..cell.0 + 1),
+            }
+        }
+        ranges
+    }
+}
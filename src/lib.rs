@@ -1,2 +1,56 @@
+pub mod automaton;
+pub mod cells;
+pub mod climate;
+pub mod cost;
+pub mod distance;
+pub mod erosion;
+pub mod generation;
+pub mod hierarchy;
+pub mod layer;
+pub mod map;
+pub mod noisy_edges;
+pub mod outlines;
+pub mod pathfinding;
+pub mod picking;
+pub mod plates;
+pub mod regions;
+pub mod render;
+pub mod rivers;
+pub mod roads;
+pub mod sampling;
+pub mod settlements;
+pub mod terrain;
 pub mod voronoi;
-pub use voronoi::{Boundary, Point, Voronoi, VoronoiBuilder};
+pub mod water;
+pub mod wfc;
+pub mod wind;
+pub use automaton::Automaton;
+pub use cells::{CellColor, CellEntities, CellEntitiesPlugin, MapCell};
+pub use climate::{Biome, BiomeId, BiomeTable, Climate, ClimateSettings};
+pub use cost::{CostWeights, TerrainCost};
+pub use erosion::{HydraulicErosion, ThermalErosion};
+pub use generation::{
+    GenerateMap, GenerationContext, MapGenerated, MapGenerationError, MapGenerationFailed,
+    MapGenerationPlugin, MapGenerationProgress, MapGenerator, MapState,
+};
+pub use hierarchy::VoronoiHierarchy;
+pub use layer::{CellLayer, CornerLayer, Layer, TrackedCellLayer};
+pub use map::VoronoiMap;
+pub use noisy_edges::{NoisyEdgeSettings, NoisyEdges};
+pub use outlines::Polygon;
+pub use pathfinding::Path;
+pub use picking::{CellClicked, CellHovered, CellPickingPlugin, CellUnhovered, HoveredCell};
+pub use plates::{Plate, PlateId, PlateSettings, Plates};
+pub use regions::{Capital, RegionId, Regions};
+pub use render::{CellColors, MapRenderPlugin, VoronoiMaterial};
+pub use rivers::{River, Rivers};
+pub use roads::{Road, RoadClass, RoadSettings, Roads};
+pub use sampling::{CellSample, Fractal, NoiseField, Warp};
+pub use settlements::{
+    Quota, Settlement, SettlementId, SettlementKind, SettlementSettings, Settlements,
+};
+pub use terrain::{Elevation, ElevationMethod};
+pub use voronoi::{Boundary, CellId, CornerId, Point, Voronoi, VoronoiBuilder};
+pub use water::{Depression, Depressions, Surface};
+pub use wfc::{Reason, TileRules, Wfc, WfcError, WfcSettings};
+pub use wind::{Wind, WindSettings};
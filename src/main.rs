@@ -1,6 +1,3 @@
-use bevy::{asset::RenderAssetUsages, prelude::*};
-use voronoice::*;
-mod voronoi;
 fn main() {}
 
 // fn main() {
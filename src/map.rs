@@ -0,0 +1,21 @@
+use bevy::prelude::*;
+
+use crate::voronoi::Voronoi;
+
+/// The voronoi map used by the bevy integration.
+///
+/// The map lives on the world XY plane (z = 0) using the same coordinates as [`Voronoi::mesh_buffers`].
+#[derive(Resource)]
+pub struct VoronoiMap {
+    voronoi: Voronoi,
+}
+
+impl VoronoiMap {
+    pub fn new(voronoi: Voronoi) -> Self {
+        Self { voronoi }
+    }
+
+    pub fn voronoi(&self) -> &Voronoi {
+        &self.voronoi
+    }
+}
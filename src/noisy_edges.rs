@@ -0,0 +1,250 @@
+use std::collections::{BTreeMap, HashMap};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    voronoi::{CellId, CellMeshRange, CornerId, Voronoi},
+};
+
+#[derive(Clone, Debug)]
+pub struct NoisyEdgeSettings {
+    /// Edges are subdivided until their segments are shorter than this.
+    pub min_length: f32,
+    /// How far the subdivisions stray from a regular split, in `[0, 1]`.
+    pub roughness: f32,
+}
+
+impl Default for NoisyEdgeSettings {
+    fn default() -> Self {
+        Self {
+            min_length: 1.0,
+            roughness: 1.0,
+        }
+    }
+}
+
+/// Cell edges recursively subdivided into wiggly polylines, for organic borders.
+///
+/// Every edge between two cells stays inside the quadrilateral formed by the two sites and the two corners, so the
+/// borders never cross. Both cells share the same polyline, edges along the boundary stay straight.
+#[derive(Clone, Debug, Default)]
+pub struct NoisyEdges {
+    /// The points between the corners of each edge, from the lower to the higher corner id.
+    edges: HashMap<(CornerId, CornerId), Vec<[f32; 2]>>,
+}
+
+impl NoisyEdges {
+    /// The subdivision of each edge is seeded from the map seed and its corners, so it does not depend on the other edges.
+    pub fn generate(voronoi: &Voronoi, settings: &NoisyEdgeSettings) -> Self {
+        let mut edge_cells: BTreeMap<(CornerId, CornerId), Vec<CellId>> = BTreeMap::new();
+        for cell in voronoi.cell_ids() {
+            let corners = voronoi.cell_corners(cell).collect::<Vec<_>>();
+            for (i, a) in corners.iter().enumerate() {
+                let b = corners[(i + 1) % corners.len()];
+                if *a != b {
+                    edge_cells.entry(edge_key(*a, b)).or_default().push(cell);
+                }
+            }
+        }
+
+        let edges = edge_cells
+            .into_iter()
+            .map(|((a, b), cells)| {
+                let mut points = Vec::new();
+                if let [first, second] = cells[..] {
+                    let mut rng = StdRng::seed_from_u64(edge_seed(voronoi.seed(), a, b));
+                    subdivide(
+                        &mut rng,
+                        settings,
+                        edge_quad(
+                            voronoi.corner_position(a),
+                            voronoi.cell_position(first.min(second)),
+                            voronoi.corner_position(b),
+                            voronoi.cell_position(first.max(second)),
+                        ),
+                        0,
+                        &mut points,
+                    );
+                }
+                ((a, b), points)
+            })
+            .collect();
+        Self { edges }
+    }
+
+    /// The polyline of the edge from corner `a` to corner `b`, including both corners.
+    pub fn edge(&self, voronoi: &Voronoi, a: CornerId, b: CornerId) -> Vec<[f32; 2]> {
+        let mut points = vec![voronoi.corner_position(a)];
+        points.extend(self.interior(a, b));
+        points.push(voronoi.corner_position(b));
+        points
+    }
+
+    /// Every edge as a polyline including its corners.
+    pub fn polylines(&self, voronoi: &Voronoi) -> Vec<Vec<[f32; 2]>> {
+        let mut keys = self.edges.keys().copied().collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys.into_iter()
+            .map(|(a, b)| self.edge(voronoi, a, b))
+            .collect()
+    }
+
+    /// The counter-clockwise outline of a cell following its noisy edges, without repeating the first point.
+    pub fn cell_outline(&self, voronoi: &Voronoi, cell: CellId) -> Vec<[f32; 2]> {
+        let corners = voronoi.cell_corners(cell).collect::<Vec<_>>();
+        let mut outline = Vec::new();
+        for (i, a) in corners.iter().enumerate() {
+            let b = corners[(i + 1) % corners.len()];
+            outline.push(voronoi.corner_position(*a));
+            outline.extend(self.interior(*a, b));
+        }
+        outline
+    }
+
+    /// Like [`Voronoi::mesh_buffers`], but the cells are filled up to their noisy edges.
+    ///
+    /// Every cell is a triangle fan from its site, where an edge bends back on itself the fans of the two cells overlap slightly.
+    pub fn mesh_buffers(
+        &self,
+        voronoi: &Voronoi,
+    ) -> (Vec<[f32; 3]>, Vec<u32>, CellLayer<CellMeshRange>) {
+        let mut vertices: Vec<[f32; 3]> = Vec::new();
+        let mut indices = Vec::new();
+        let mut ranges = Vec::with_capacity(voronoi.cell_count());
+        let mut corner_vertices: HashMap<CornerId, u32> = HashMap::new();
+        let mut edge_vertices: HashMap<(CornerId, CornerId), u32> = HashMap::new();
+
+        for cell in voronoi.cell_ids() {
+            let indices_start = indices.len();
+            let center = vertices.len() as u32;
+            let [x, y] = voronoi.cell_position(cell);
+            vertices.push([x, y, 0.0]);
+
+            let corners = voronoi.cell_corners(cell).collect::<Vec<_>>();
+            let mut outline: Vec<u32> = Vec::new();
+            for (i, a) in corners.iter().enumerate() {
+                let b = corners[(i + 1) % corners.len()];
+                outline.push(*corner_vertices.entry(*a).or_insert_with(|| {
+                    let [x, y] = voronoi.corner_position(*a);
+                    vertices.push([x, y, 0.0]);
+                    vertices.len() as u32 - 1
+                }));
+                let key = edge_key(*a, b);
+                let interior = self.edges.get(&key).map_or(&[][..], Vec::as_slice);
+                // the points of an edge are added once, by the first cell that reaches it
+                let first = *edge_vertices.entry(key).or_insert_with(|| {
+                    let first = vertices.len() as u32;
+                    vertices.extend(interior.iter().map(|[x, y]| [*x, *y, 0.0]));
+                    first
+                });
+                let edge = (first..first + interior.len() as u32).collect::<Vec<_>>();
+                if key.0 == *a {
+                    outline.extend(edge);
+                } else {
+                    outline.extend(edge.into_iter().rev());
+                }
+            }
+            for (i, from) in outline.iter().enumerate() {
+                indices.extend([*from, outline[(i + 1) % outline.len()], center]);
+            }
+            ranges.push(CellMeshRange {
+                indices: indices_start..indices.len(),
+                vertices: center as usize..vertices.len(),
+            });
+        }
+        (vertices, indices, CellLayer::from_vec(ranges))
+    }
+
+    /// The points between `a` and `b`, in the direction from `a` to `b`.
+    fn interior(&self, a: CornerId, b: CornerId) -> impl Iterator<Item = [f32; 2]> + '_ {
+        let points = self
+            .edges
+            .get(&edge_key(a, b))
+            .map_or(&[][..], Vec::as_slice);
+        let reversed = a > b;
+        (0..points.len()).map(move |i| points[if reversed { points.len() - 1 - i } else { i }])
+    }
+}
+
+fn edge_key(a: CornerId, b: CornerId) -> (CornerId, CornerId) {
+    (a.min(b), a.max(b))
+}
+
+fn edge_seed(seed: u64, a: CornerId, b: CornerId) -> u64 {
+    seed ^ ((a.0 as u64) << 32 | b.0 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+/// The quad the edge from `a` to `b` between the sites `s` and `t` is subdivided in.
+///
+/// The quad of the sites is made of the triangles the edge forms with each site, which lie inside the two cells. When it
+/// is not convex the subdivision could leave it, so the sites are replaced by the points of the triangles straight
+/// above and below the middle of the edge.
+fn edge_quad(a: [f32; 2], s: [f32; 2], b: [f32; 2], t: [f32; 2]) -> [[f32; 2]; 4] {
+    let side = |[px, py]: [f32; 2]| (t[0] - s[0]) * (py - s[1]) - (t[1] - s[1]) * (px - s[0]);
+    if side(a) * side(b) < 0.0 {
+        return [a, s, b, t];
+    }
+    let middle = lerp(a, b, 0.5);
+    let apex = |p: [f32; 2]| {
+        let along = ((p[0] - a[0]) * (b[0] - a[0]) + (p[1] - a[1]) * (b[1] - a[1]))
+            / distance(a, b).powi(2);
+        let foot = lerp(a, b, along);
+        // the height of the triangle above the middle, relative to the height of `p`
+        let scale = 0.5 / along.max(1.0 - along);
+        [
+            middle[0] + (p[0] - foot[0]) * scale,
+            middle[1] + (p[1] - foot[1]) * scale,
+        ]
+    };
+    [a, apex(s), b, apex(t)]
+}
+
+/// The deepest recursion, in case `min_length` is tiny compared to the edges.
+const MAX_DEPTH: usize = 12;
+
+/// Add the points between `a` and `c` to `points`, staying inside the quad `a`, `b`, `c`, `d`.
+fn subdivide(
+    rng: &mut StdRng,
+    settings: &NoisyEdgeSettings,
+    [a, b, c, d]: [[f32; 2]; 4],
+    depth: usize,
+    points: &mut Vec<[f32; 2]>,
+) {
+    if depth >= MAX_DEPTH
+        || distance(a, c) < settings.min_length
+        || distance(b, d) < settings.min_length
+    {
+        return;
+    }
+    let roughness = settings.roughness.clamp(0.0, 1.0);
+    let mut jitter = |range: f32| rng.gen_range(-range..=range) * roughness;
+    let (p, q) = (0.5 + jitter(0.3), 0.5 + jitter(0.3));
+    let h = lerp(lerp(a, d, p), lerp(b, c, p), q);
+    let (s, t) = (1.0 + jitter(0.4), 1.0 + jitter(0.4));
+    // the control points of the halves stay on the sides of this quad, so the halves stay inside it
+    let side = |from, to, share: f32| lerp(from, to, share.min(1.0));
+    subdivide(
+        rng,
+        settings,
+        [a, side(b, a, s * (1.0 - q)), h, side(d, a, t * (1.0 - p))],
+        depth + 1,
+        points,
+    );
+    points.push(h);
+    subdivide(
+        rng,
+        settings,
+        [h, side(c, b, s * (1.0 - p)), c, side(d, c, t * q)],
+        depth + 1,
+        points,
+    );
+}
+
+fn lerp([ax, ay]: [f32; 2], [bx, by]: [f32; 2], t: f32) -> [f32; 2] {
+    [ax + (bx - ax) * t, ay + (by - ay) * t]
+}
+
+fn distance([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    (ax - bx).hypot(ay - by)
+}
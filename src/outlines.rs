@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use crate::{
+    layer::CellLayer,
+    voronoi::{CellId, CornerId, Voronoi},
+};
+
+/// A closed outline of merged cells, the outer ring runs counter-clockwise and the holes clockwise.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Polygon {
+    pub outer: Vec<CornerId>,
+    pub holes: Vec<Vec<CornerId>>,
+}
+
+impl Polygon {
+    pub fn outer_positions(&self, voronoi: &Voronoi) -> Vec<[f32; 2]> {
+        positions(voronoi, &self.outer)
+    }
+
+    pub fn hole_positions(&self, voronoi: &Voronoi) -> Vec<Vec<[f32; 2]>> {
+        self.holes
+            .iter()
+            .map(|hole| positions(voronoi, hole))
+            .collect()
+    }
+
+    /// The area inside the outer ring and outside the holes.
+    pub fn area(&self, voronoi: &Voronoi) -> f32 {
+        signed_area(&self.outer_positions(voronoi))
+            + self
+                .hole_positions(voronoi)
+                .iter()
+                .map(|hole| signed_area(hole))
+                .sum::<f32>()
+    }
+
+    /// The centre of mass of the polygon, it can lie outside of concave polygons, see [`label_cell`] for labels.
+    pub fn centroid(&self, voronoi: &Voronoi) -> [f32; 2] {
+        let rings =
+            std::iter::once(self.outer_positions(voronoi)).chain(self.hole_positions(voronoi));
+        let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+        for ring in rings {
+            for (i, [ax, ay]) in ring.iter().enumerate() {
+                let [bx, by] = ring[(i + 1) % ring.len()];
+                let cross = ax * by - bx * ay;
+                area += cross;
+                x += (ax + bx) * cross;
+                y += (ay + by) * cross;
+            }
+        }
+        if area.abs() <= f32::EPSILON {
+            return self
+                .outer_positions(voronoi)
+                .first()
+                .copied()
+                .unwrap_or_default();
+        }
+        [x / (3.0 * area), y / (3.0 * area)]
+    }
+
+    /// The polygon as svg path data, to be filled with `fill-rule="evenodd"`.
+    ///
+    /// The y-axis is flipped, svg y points down while the map y points up.
+    pub fn svg_path(&self, voronoi: &Voronoi) -> String {
+        let mut path = String::new();
+        let rings =
+            std::iter::once(self.outer_positions(voronoi)).chain(self.hole_positions(voronoi));
+        for ring in rings {
+            for (i, [x, y]) in ring.iter().enumerate() {
+                let command = if i == 0 { 'M' } else { 'L' };
+                let _ = write!(path, "{command}{x} {} ", -y);
+            }
+            path.push_str("Z ");
+        }
+        path.truncate(path.trim_end().len());
+        path
+    }
+}
+
+/// The outlines of the cells for which `inside` is true, one polygon per connected group.
+pub fn outline(voronoi: &Voronoi, inside: impl Fn(CellId) -> bool) -> Vec<Polygon> {
+    polygons(voronoi, voronoi.boundary_loops(inside))
+}
+
+/// The outlines of every group of cells with the same label, e.g. the [`Regions::owner`](crate::regions::Regions::owner) layer.
+///
+/// All labels are outlined in a single pass over the cells. For a layer of `Option`s the `None` cells are outlined as a
+/// group of their own.
+pub fn outlines<T: Ord + Clone>(
+    voronoi: &Voronoi,
+    labels: &CellLayer<T>,
+) -> BTreeMap<T, Vec<Polygon>> {
+    voronoi
+        .labelled_boundary_loops(|cell| Some(labels[cell].clone()))
+        .into_iter()
+        .map(|(label, loops)| (label, polygons(voronoi, loops)))
+        .collect()
+}
+
+/// Group the boundary loops of one group of cells into polygons, holes go to the smallest outer ring around them.
+fn polygons(voronoi: &Voronoi, loops: Vec<Vec<CornerId>>) -> Vec<Polygon> {
+    let (outers, holes): (Vec<_>, Vec<_>) = loops
+        .into_iter()
+        .map(|ring| {
+            let area = signed_area(&positions(voronoi, &ring));
+            (ring, area)
+        })
+        .partition(|(_, area)| *area > 0.0);
+
+    let mut polygons: Vec<Polygon> = outers
+        .iter()
+        .map(|(outer, _)| Polygon {
+            outer: outer.clone(),
+            holes: Vec::new(),
+        })
+        .collect();
+    for (hole, _) in holes {
+        // an edge belongs to a single ring, so the middle of one lies strictly inside or outside every other ring
+        let [ax, ay] = voronoi.corner_position(hole[0]);
+        let [bx, by] = voronoi.corner_position(hole[1 % hole.len()]);
+        let point = [(ax + bx) / 2.0, (ay + by) / 2.0];
+        let container = outers
+            .iter()
+            .enumerate()
+            .filter(|(_, (outer, _))| contains(&positions(voronoi, outer), point))
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map(|(i, _)| i);
+        if let Some(i) = container {
+            polygons[i].holes.push(hole);
+        }
+    }
+    polygons
+}
+
+/// The cell of a group that is the most steps away from its border, a good place for a label.
+pub fn label_cell(voronoi: &Voronoi, inside: impl Fn(CellId) -> bool) -> Option<CellId> {
+    let mut depth: CellLayer<Option<usize>> = CellLayer::from_fn(voronoi, |cell| {
+        (!inside(cell) || voronoi.is_boundary_cell(cell)).then_some(0)
+    });
+    let mut frontier: Vec<CellId> = voronoi.cell_ids().filter(|c| depth[*c].is_some()).collect();
+    let mut deepest = None;
+    let mut level = 0;
+    while !frontier.is_empty() {
+        level += 1;
+        let mut next = Vec::new();
+        for cell in frontier {
+            for neighbor in voronoi.cell_neighbors(cell) {
+                if depth[neighbor].is_none() {
+                    depth[neighbor] = Some(level);
+                    deepest = Some(neighbor);
+                    next.push(neighbor);
+                }
+            }
+        }
+        frontier = next;
+    }
+    // groups that are all border have no deeper cell
+    deepest.or_else(|| voronoi.cell_ids().find(|cell| inside(*cell)))
+}
+
+fn positions(voronoi: &Voronoi, ring: &[CornerId]) -> Vec<[f32; 2]> {
+    ring.iter()
+        .map(|corner| voronoi.corner_position(*corner))
+        .collect()
+}
+
+fn signed_area(ring: &[[f32; 2]]) -> f32 {
+    ring.iter()
+        .enumerate()
+        .map(|(i, [ax, ay])| {
+            let [bx, by] = ring[(i + 1) % ring.len()];
+            ax * by - bx * ay
+        })
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Even-odd point in polygon test.
+fn contains(ring: &[[f32; 2]], [x, y]: [f32; 2]) -> bool {
+    let mut inside = false;
+    for (i, [ax, ay]) in ring.iter().enumerate() {
+        let [bx, by] = ring[(i + 1) % ring.len()];
+        if (*ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+            inside = !inside;
+        }
+    }
+    inside
+}
@@ -0,0 +1,295 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{
+    layer::CellLayer,
+    voronoi::{CellId, Voronoi},
+};
+
+/// A path between two cells and the sum of the costs of its steps.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    /// The cells from the start to the goal, both included.
+    pub cells: Vec<CellId>,
+    pub cost: f32,
+}
+
+/// The least-cost path from `start` to the nearest cell for which `is_goal` is true.
+///
+/// `cost` is the cost of moving between two neighbouring cells, it must not be negative. `None` makes the move
+/// impossible, e.g. into water.
+pub fn dijkstra(
+    voronoi: &Voronoi,
+    start: CellId,
+    is_goal: impl Fn(CellId) -> bool,
+    cost: impl Fn(CellId, CellId) -> Option<f32>,
+) -> Option<Path> {
+    let search = Search::run(
+        voronoi,
+        [start],
+        f32::INFINITY,
+        is_goal,
+        |_, from, to| cost(from, to),
+        |_| 0.0,
+    );
+    search.path()
+}
+
+/// The least-cost path from `start` to `goal`, searching towards the goal first.
+///
+/// `heuristic` estimates the cost from a cell to the goal, the path is the cheapest if it never overestimates and
+/// does not drop by more than the cost of a step, e.g. the straight line distance times the lowest cost per unit of
+/// distance. See [`dijkstra`] for `cost`.
+pub fn astar(
+    voronoi: &Voronoi,
+    start: CellId,
+    goal: CellId,
+    cost: impl Fn(CellId, CellId) -> Option<f32>,
+    heuristic: impl Fn(CellId) -> f32,
+) -> Option<Path> {
+    let search = Search::run(
+        voronoi,
+        [start],
+        f32::INFINITY,
+        |cell| cell == goal,
+        |_, from, to| cost(from, to),
+        heuristic,
+    );
+    search.path()
+}
+
+/// The least cost from any of the `sources` to every cell, `f32::INFINITY` for cells that cannot be reached.
+///
+/// See [`dijkstra`] for `cost`.
+pub fn distance_field(
+    voronoi: &Voronoi,
+    sources: impl IntoIterator<Item = CellId>,
+    cost: impl Fn(CellId, CellId) -> Option<f32>,
+) -> CellLayer<f32> {
+    Search::run(
+        voronoi,
+        sources,
+        f32::INFINITY,
+        |_| false,
+        |_, from, to| cost(from, to),
+        |_| 0.0,
+    )
+    .distance
+}
+
+/// The least cost from the nearest of `sources` to every cell and the index of that source, e.g. to grow regions.
+///
+/// `cost` also gets the index of the source a path starts from, so every source can spread at its own pace. Cells that
+/// cost more than `max_cost` to reach are left at `f32::INFINITY` without a source. See [`dijkstra`] for `cost`.
+pub fn nearest_source(
+    voronoi: &Voronoi,
+    sources: &[CellId],
+    max_cost: f32,
+    cost: impl Fn(usize, CellId, CellId) -> Option<f32>,
+) -> (CellLayer<f32>, CellLayer<Option<usize>>) {
+    let search = Search::run(
+        voronoi,
+        sources.iter().copied(),
+        max_cost,
+        |_| false,
+        cost,
+        |_| 0.0,
+    );
+    (search.distance, search.source)
+}
+
+/// An element waiting in a queue ordered by cost, a [`BinaryHeap`] pops the lowest cost first and ties by the lowest id.
+pub(crate) struct MinHeapEntry<I> {
+    pub(crate) cost: f32,
+    pub(crate) id: I,
+}
+
+impl<I: Ord> PartialEq for MinHeapEntry<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<I: Ord> Eq for MinHeapEntry<I> {}
+
+impl<I: Ord> PartialOrd for MinHeapEntry<I> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I: Ord> Ord for MinHeapEntry<I> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .total_cmp(&self.cost)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+struct Search {
+    distance: CellLayer<f32>,
+    previous: CellLayer<Option<CellId>>,
+    /// The index of the source every cell was reached from.
+    source: CellLayer<Option<usize>>,
+    goal: Option<CellId>,
+}
+
+impl Search {
+    /// Expand cells from the sources in order of cost plus heuristic until a goal is reached or all cells within
+    /// `max_cost` are done.
+    fn run(
+        voronoi: &Voronoi,
+        sources: impl IntoIterator<Item = CellId>,
+        max_cost: f32,
+        is_goal: impl Fn(CellId) -> bool,
+        cost: impl Fn(usize, CellId, CellId) -> Option<f32>,
+        heuristic: impl Fn(CellId) -> f32,
+    ) -> Self {
+        let mut search = Self {
+            distance: CellLayer::new(voronoi, f32::INFINITY),
+            previous: CellLayer::new(voronoi, None),
+            source: CellLayer::new(voronoi, None),
+            goal: None,
+        };
+        let mut done = CellLayer::new(voronoi, false);
+        let mut queue = BinaryHeap::new();
+        for (index, source) in sources.into_iter().enumerate() {
+            // a cell listed twice belongs to its first source
+            if search.distance[source] > 0.0 {
+                search.distance[source] = 0.0;
+                search.source[source] = Some(index);
+                queue.push(MinHeapEntry {
+                    cost: heuristic(source),
+                    id: source,
+                });
+            }
+        }
+        while let Some(MinHeapEntry { id: cell, .. }) = queue.pop() {
+            if done[cell] {
+                continue;
+            }
+            done[cell] = true;
+            if is_goal(cell) {
+                search.goal = Some(cell);
+                return search;
+            }
+            let Some(source) = search.source[cell] else {
+                continue;
+            };
+            for neighbor in voronoi.cell_neighbors(cell) {
+                if done[neighbor] {
+                    continue;
+                }
+                let Some(step) = cost(source, cell, neighbor) else {
+                    continue;
+                };
+                let reached = search.distance[cell] + step;
+                if reached <= max_cost && reached < search.distance[neighbor] {
+                    search.distance[neighbor] = reached;
+                    search.previous[neighbor] = Some(cell);
+                    search.source[neighbor] = Some(source);
+                    queue.push(MinHeapEntry {
+                        cost: reached + heuristic(neighbor),
+                        id: neighbor,
+                    });
+                }
+            }
+        }
+        search
+    }
+
+    fn path(&self) -> Option<Path> {
+        let goal = self.goal?;
+        let mut cells = vec![goal];
+        while let Some(cell) = self.previous[*cells.last()?] {
+            cells.push(cell);
+        }
+        cells.reverse();
+        Some(Path {
+            cells,
+            cost: self.distance[goal],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voronoi::{Boundary, VoronoiBuilder};
+
+    fn voronoi() -> Voronoi {
+        VoronoiBuilder::default()
+            .set_seed(3)
+            .set_sites_random(Boundary::CenteredSquare(100.0), 1000)
+            .set_lloyd_relaxation_iterations(1)
+            .build()
+    }
+
+    fn distance(voronoi: &Voronoi, from: CellId, to: CellId) -> f32 {
+        let [ax, ay] = voronoi.cell_position(from);
+        let [bx, by] = voronoi.cell_position(to);
+        (bx - ax).hypot(by - ay)
+    }
+
+    #[test]
+    fn astar_dijkstra_and_distance_field_agree() {
+        let voronoi = voronoi();
+        // the east of the map costs twice as much and a band in the middle is impassable
+        let cost = |from, to| {
+            let [x, y] = voronoi.cell_position(to);
+            if x.abs() < 5.0 && y > -30.0 {
+                return None;
+            }
+            Some(distance(&voronoi, from, to) * if x > 0.0 { 2.0 } else { 1.0 })
+        };
+        let start = CellId(0);
+        let field = distance_field(&voronoi, [start], cost);
+        for goal in (0..voronoi.cell_count()).step_by(97).map(CellId) {
+            let by_dijkstra = dijkstra(&voronoi, start, |cell| cell == goal, cost);
+            let by_astar = astar(&voronoi, start, goal, cost, |cell| {
+                distance(&voronoi, cell, goal)
+            });
+            let (Some(by_dijkstra), Some(by_astar)) = (by_dijkstra, by_astar) else {
+                panic!("{goal:?} is reachable around the band");
+            };
+            assert!((by_dijkstra.cost - by_astar.cost).abs() < 1e-3);
+            assert!((by_dijkstra.cost - field[goal]).abs() < 1e-3);
+
+            assert_eq!(by_astar.cells.first(), Some(&start));
+            assert_eq!(by_astar.cells.last(), Some(&goal));
+            let steps: f32 = by_astar
+                .cells
+                .windows(2)
+                .map(|step| {
+                    assert!(voronoi.cell_neighbors(step[0]).any(|n| n == step[1]));
+                    cost(step[0], step[1]).expect("the path only takes passable steps")
+                })
+                .sum();
+            assert!((steps - by_astar.cost).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn nearest_source_stops_at_max_cost() {
+        let voronoi = voronoi();
+        let sources = [CellId(0), CellId(500)];
+        let cost = |from, to| Some(distance(&voronoi, from, to));
+        let (reached, source) =
+            nearest_source(&voronoi, &sources, 20.0, |_, from, to| cost(from, to));
+        let fields = sources.map(|source| distance_field(&voronoi, [source], cost));
+        for cell in voronoi.cell_ids() {
+            let nearest = fields[0][cell].min(fields[1][cell]);
+            match source[cell] {
+                Some(i) => {
+                    assert!(reached[cell] <= 20.0);
+                    assert!((fields[i][cell] - nearest).abs() < 1e-3);
+                    assert!((reached[cell] - nearest).abs() < 1e-3);
+                }
+                None => {
+                    assert!(nearest > 20.0);
+                    assert_eq!(reached[cell], f32::INFINITY);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::{input::InputSystem, prelude::*, window::PrimaryWindow};
+
+use crate::{map::VoronoiMap, voronoi::CellId};
+
+/// Resolves the cell under the cursor of the primary window and emits [`CellHovered`], [`CellUnhovered`] and [`CellClicked`] events.
+///
+/// The cursor is projected through the active [`Camera2d`] or [`Camera3d`] onto the map plane (see [`VoronoiMap`]).
+/// Picking runs in [`PreUpdate`] so the events can be read in [`Update`] of the same frame.
+pub struct CellPickingPlugin;
+
+impl Plugin for CellPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HoveredCell>()
+            .init_resource::<CellHighlight>()
+            .add_event::<CellHovered>()
+            .add_event::<CellUnhovered>()
+            .add_event::<CellClicked>()
+            .add_systems(
+                PreUpdate,
+                (pick_cells, click_cells)
+                    .chain()
+                    .after(InputSystem)
+                    .run_if(resource_exists::<VoronoiMap>),
+            )
+            .add_systems(
+                Update,
+                highlight_hovered_cell.run_if(resource_exists::<VoronoiMap>),
+            );
+    }
+}
+
+/// The cell currently under the cursor, if any.
+#[derive(Resource, Default, Debug)]
+pub struct HoveredCell {
+    pub cell: Option<CellId>,
+    /// The cursor position in map space.
+    pub position: Option<Vec2>,
+}
+
+/// Outline the hovered cell with gizmos.
+#[derive(Resource, Debug)]
+pub struct CellHighlight {
+    pub enabled: bool,
+    pub color: Color,
+}
+
+impl Default for CellHighlight {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// The cursor entered `cell`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CellHovered {
+    pub cell: CellId,
+    pub position: Vec2,
+}
+
+/// The cursor left `cell`, either to another cell or off the map.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CellUnhovered {
+    pub cell: CellId,
+}
+
+/// A mouse button was pressed over `cell`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CellClicked {
+    pub cell: CellId,
+    pub button: MouseButton,
+    pub position: Vec2,
+}
+
+/// Project a viewport position onto the map plane, the result is in map space.
+pub fn viewport_to_map(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    viewport_position: Vec2,
+) -> Option<Vec2> {
+    let ray = camera
+        .viewport_to_world(camera_transform, viewport_position)
+        .ok()?;
+    let distance = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Z))?;
+    Some(ray.get_point(distance).truncate())
+}
+
+type MapCamera = Or<(With<Camera2d>, With<Camera3d>)>;
+
+fn pick_cells(
+    map: Res<VoronoiMap>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), MapCamera>,
+    mut hovered: ResMut<HoveredCell>,
+    mut hovered_events: EventWriter<CellHovered>,
+    mut unhovered_events: EventWriter<CellUnhovered>,
+) {
+    if map.is_changed() {
+        // the hovered cell belongs to the previous map, forget it without events
+        *hovered = HoveredCell::default();
+    }
+    let position = windows
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .and_then(|cursor| {
+            // the active camera is the top most camera whose viewport contains the cursor
+            cameras
+                .iter()
+                .filter(|(camera, _)| camera.is_active)
+                .filter(|(camera, _)| {
+                    camera
+                        .logical_viewport_rect()
+                        .is_some_and(|rect| rect.contains(cursor))
+                })
+                .max_by_key(|(camera, _)| camera.order)
+                .and_then(|(camera, transform)| viewport_to_map(camera, transform, cursor))
+        });
+
+    let cell = position.and_then(|position| {
+        let hint = hovered.cell.unwrap_or(CellId(0));
+        map.voronoi().cell_at_near((position.x, position.y), hint)
+    });
+
+    if cell != hovered.cell {
+        if let Some(previous) = hovered.cell {
+            unhovered_events.send(CellUnhovered { cell: previous });
+        }
+        if let (Some(cell), Some(position)) = (cell, position) {
+            hovered_events.send(CellHovered { cell, position });
+        }
+    }
+    hovered.cell = cell;
+    hovered.position = position;
+}
+
+fn click_cells(
+    hovered: Res<HoveredCell>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut clicked_events: EventWriter<CellClicked>,
+) {
+    if let (Some(cell), Some(position)) = (hovered.cell, hovered.position) {
+        for button in buttons.get_just_pressed() {
+            clicked_events.send(CellClicked {
+                cell,
+                button: *button,
+                position,
+            });
+        }
+    }
+}
+
+fn highlight_hovered_cell(
+    mut gizmos: Gizmos,
+    map: Res<VoronoiMap>,
+    hovered: Res<HoveredCell>,
+    highlight: Res<CellHighlight>,
+) {
+    let Some(cell) = hovered.cell.filter(|_| highlight.enabled) else {
+        return;
+    };
+    let vertices = map.voronoi().cell_vertices(cell);
+    let first = vertices.first().copied();
+    gizmos.linestrip_2d(
+        vertices.into_iter().chain(first).map(Vec2::from),
+        highlight.color,
+    );
+}
@@ -0,0 +1,166 @@
+use std::{collections::VecDeque, f32::consts::TAU};
+
+use rand::{rngs::StdRng, seq::index::sample, Rng, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    terrain::Elevation,
+    voronoi::{CellId, Voronoi},
+};
+
+#[derive(Clone, Debug)]
+pub struct PlateSettings {
+    pub seed: u64,
+    pub count: usize,
+    /// The elevation added where plates collide head on.
+    pub uplift: f32,
+    /// The elevation removed where plates move apart.
+    pub rift_depth: f32,
+    /// How many cells away from a plate boundary the uplift or rift reaches.
+    pub reach: usize,
+}
+
+impl Default for PlateSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            count: 8,
+            uplift: 0.4,
+            rift_depth: 0.2,
+            reach: 4,
+        }
+    }
+}
+
+/// An index into [`Plates::plates`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct PlateId(pub usize);
+
+#[derive(Clone, Debug)]
+pub struct Plate {
+    /// The cell the plate was grown from.
+    pub seed: CellId,
+    /// The direction and speed the plate moves in, the speed is at most 1.
+    pub motion: [f32; 2],
+}
+
+/// The cells partitioned into moving plates, see [`Plates::apply`].
+#[derive(Clone, Debug, Default)]
+pub struct Plates {
+    pub plates: Vec<Plate>,
+    pub owner: CellLayer<PlateId>,
+    /// How fast each boundary cell is pushed into (positive) or pulled away from (negative) the neighbouring plates, 0 inside plates.
+    pub stress: CellLayer<f32>,
+    /// The elevation change from the plate boundaries, fading out over [`PlateSettings::reach`] cells.
+    pub offset: CellLayer<f32>,
+}
+
+impl Plates {
+    pub fn generate(voronoi: &Voronoi, settings: &PlateSettings) -> Self {
+        let mut rng = StdRng::seed_from_u64(settings.seed);
+        let cell_count = voronoi.cell_count();
+        let plates: Vec<Plate> = sample(&mut rng, cell_count, settings.count.min(cell_count))
+            .into_iter()
+            .map(|i| {
+                let angle = rng.gen_range(0.0..TAU);
+                let speed = rng.gen_range(0.0..=1.0f32);
+                Plate {
+                    seed: CellId(i),
+                    motion: [speed * angle.cos(), speed * angle.sin()],
+                }
+            })
+            .collect();
+
+        // grow from a random frontier cell each step, so plates get irregular outlines
+        let mut owner: CellLayer<Option<PlateId>> = CellLayer::new(voronoi, None);
+        let mut frontier: Vec<CellId> = Vec::new();
+        for (i, plate) in plates.iter().enumerate() {
+            owner[plate.seed] = Some(PlateId(i));
+            frontier.push(plate.seed);
+        }
+        while !frontier.is_empty() {
+            let cell = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            for neighbor in voronoi.cell_neighbors(cell) {
+                if owner[neighbor].is_none() {
+                    owner[neighbor] = owner[cell];
+                    frontier.push(neighbor);
+                }
+            }
+        }
+        let owner = owner.map(|plate| plate.unwrap_or_default());
+
+        let stress = CellLayer::from_fn(voronoi, |cell| {
+            let plate = owner[cell];
+            let [x, y] = voronoi.cell_position(cell);
+            let pressures: Vec<f32> = voronoi
+                .cell_neighbors(cell)
+                .filter(|neighbor| owner[*neighbor] != plate)
+                .map(|neighbor| {
+                    let [nx, ny] = voronoi.cell_position(neighbor);
+                    let distance = (nx - x).hypot(ny - y).max(f32::EPSILON);
+                    let [mx, my] = plates[plate.0].motion;
+                    let [ox, oy] = plates[owner[neighbor].0].motion;
+                    // the closing speed along the line between the two cells
+                    ((mx - ox) * (nx - x) + (my - oy) * (ny - y)) / distance
+                })
+                .collect();
+            if pressures.is_empty() {
+                return 0.0;
+            }
+            pressures.iter().sum::<f32>() / pressures.len() as f32
+        });
+
+        // spread the boundary stress inwards, keeping the stress of the nearest boundary cell
+        let mut nearest: CellLayer<Option<(CellId, usize)>> = CellLayer::new(voronoi, None);
+        let mut queue: VecDeque<CellId> = VecDeque::new();
+        for cell in voronoi.cell_ids().filter(|c| stress[*c] != 0.0) {
+            nearest[cell] = Some((cell, 0));
+            queue.push_back(cell);
+        }
+        while let Some(cell) = queue.pop_front() {
+            let Some((source, hops)) = nearest[cell] else {
+                continue;
+            };
+            if hops >= settings.reach {
+                continue;
+            }
+            for neighbor in voronoi.cell_neighbors(cell) {
+                if nearest[neighbor].is_none() {
+                    nearest[neighbor] = Some((source, hops + 1));
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        let offset = nearest.map(|nearest| {
+            let Some((source, hops)) = nearest else {
+                return 0.0;
+            };
+            let falloff = 1.0 - *hops as f32 / (settings.reach + 1) as f32;
+            let stress = stress[*source].clamp(-2.0, 2.0) / 2.0;
+            let scale = if stress > 0.0 {
+                settings.uplift
+            } else {
+                settings.rift_depth
+            };
+            stress * scale * falloff
+        });
+
+        Self {
+            plates,
+            owner,
+            stress,
+            offset,
+        }
+    }
+
+    /// Add the boundary uplift and rifts to the cell elevation, keeping it in `[0, 1]`, and derive the corners again.
+    pub fn apply(&self, voronoi: &Voronoi, elevation: &mut Elevation) {
+        let mut cells = elevation.cells.clone();
+        cells
+            .values_mut()
+            .iter_mut()
+            .zip(self.offset.values())
+            .for_each(|(e, offset)| *e = (*e + offset).clamp(0.0, 1.0));
+        *elevation = Elevation::from_cells(voronoi, cells);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeSet;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    pathfinding::nearest_source,
+    voronoi::{CellId, Voronoi},
+};
+
+/// An index into [`Regions::capitals`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct RegionId(pub usize);
+
+/// The cell a region grows from.
+#[derive(Clone, Copy, Debug)]
+pub struct Capital {
+    pub cell: CellId,
+    /// How far the region spreads, the growth cost is divided by the strength.
+    pub strength: f32,
+}
+
+impl Capital {
+    pub fn new(cell: CellId) -> Self {
+        Self {
+            cell,
+            strength: 1.0,
+        }
+    }
+
+    /// Pick up to `count` capitals with strength 1 from the cells allowed by `candidate`, seeded from the map seed.
+    ///
+    /// Capitals are at least `min_spacing` apart, fewer are returned if there is no room for more.
+    pub fn random(
+        voronoi: &Voronoi,
+        count: usize,
+        min_spacing: f32,
+        salt: u64,
+        candidate: impl Fn(CellId) -> bool,
+    ) -> Vec<Capital> {
+        let mut rng = StdRng::seed_from_u64(voronoi.seed() ^ salt);
+        let mut cells: Vec<CellId> = voronoi.cell_ids().filter(|c| candidate(*c)).collect();
+        cells.shuffle(&mut rng);
+        let mut capitals: Vec<Capital> = Vec::new();
+        for cell in cells {
+            if capitals.len() == count {
+                break;
+            }
+            let [x, y] = voronoi.cell_position(cell);
+            let spaced = capitals.iter().all(|capital| {
+                let [cx, cy] = voronoi.cell_position(capital.cell);
+                (cx - x).hypot(cy - y) >= min_spacing
+            });
+            if spaced {
+                capitals.push(Capital::new(cell));
+            }
+        }
+        capitals
+    }
+}
+
+/// Cells partitioned into regions grown from capitals, e.g. kingdoms or guild territories.
+#[derive(Clone, Debug, Default)]
+pub struct Regions {
+    pub capitals: Vec<Capital>,
+    /// The region of every cell, `None` for cells no region could reach.
+    pub owner: CellLayer<Option<RegionId>>,
+    /// The growth cost from the capital of the owning region, divided by its strength.
+    pub distance: CellLayer<f32>,
+    /// The regions that share a border with each region, sorted.
+    pub adjacency: Vec<Vec<RegionId>>,
+}
+
+impl Regions {
+    /// Grow all regions at once, every cell goes to the region that reaches it at the lowest cost.
+    ///
+    /// `cost` is the cost of moving between two neighbouring cells, `None` stops the growth (e.g. into water).
+    /// Regions stop growing at `max_cost`, use `f32::INFINITY` to claim every reachable cell.
+    pub fn grow(
+        voronoi: &Voronoi,
+        capitals: Vec<Capital>,
+        max_cost: f32,
+        cost: impl Fn(CellId, CellId) -> Option<f32>,
+    ) -> Self {
+        let sources: Vec<CellId> = capitals.iter().map(|capital| capital.cell).collect();
+        let (distance, source) = nearest_source(voronoi, &sources, max_cost, |region, from, to| {
+            Some(cost(from, to)? / capitals[region].strength.max(f32::EPSILON))
+        });
+        let owner = source.map(|source| source.map(RegionId));
+
+        let mut borders = vec![BTreeSet::new(); capitals.len()];
+        for (cell, region) in owner.iter() {
+            let Some(region) = region else {
+                continue;
+            };
+            for neighbor in voronoi.cell_neighbors(cell) {
+                match owner[neighbor] {
+                    Some(other) if other != *region => {
+                        borders[region.0].insert(other);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Self {
+            capitals,
+            owner,
+            distance,
+            adjacency: borders
+                .into_iter()
+                .map(|b| b.into_iter().collect())
+                .collect(),
+        }
+    }
+
+    pub fn region_count(&self) -> usize {
+        self.capitals.len()
+    }
+
+    pub fn cells(&self, region: RegionId) -> impl Iterator<Item = CellId> + '_ {
+        self.owner
+            .iter()
+            .filter(move |(_, owner)| **owner == Some(region))
+            .map(|(cell, _)| cell)
+    }
+
+    pub fn neighbors(&self, region: RegionId) -> &[RegionId] {
+        &self.adjacency[region.0]
+    }
+}
@@ -0,0 +1,279 @@
+use std::ops::Range;
+
+use bevy::{
+    asset::{load_internal_asset, RenderAssetUsages},
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    render::{
+        mesh::{Indices, MeshVertexAttribute, MeshVertexBufferLayoutRef, PrimitiveTopology},
+        render_asset::RenderAssets,
+        render_resource::{
+            AsBindGroup, BufferUsages, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError, VertexFormat,
+        },
+        renderer::RenderQueue,
+        storage::{GpuShaderStorageBuffer, ShaderStorageBuffer},
+        ExtractSchedule, MainWorld, Render, RenderApp, RenderSet,
+    },
+};
+
+use crate::{
+    layer::{CellLayer, TrackedCellLayer},
+    map::VoronoiMap,
+    noisy_edges::NoisyEdges,
+    voronoi::{CellId, CellMeshRange, Voronoi},
+};
+
+pub const VORONOI_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5f1c_2b7e_9a44_4d0b_8e63_c1a9_07f2_d35e);
+
+/// The cell index of a vertex, only the cell site (the first vertex of each triangle, see [`voronoi_mesh`]) is meaningful.
+pub const ATTRIBUTE_CELL: MeshVertexAttribute =
+    MeshVertexAttribute::new("Voronoi_Cell", 9_112_402_471, VertexFormat::Uint32);
+
+pub const DEFAULT_CELL_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+
+/// Renders the [`VoronoiMap`] as a mesh with one flat colour per cell, see [`CellColors`].
+///
+/// The mesh uses a [`VoronoiMaterial`] and is only visible to 3d cameras.
+/// Only the cells whose colour changed are re-uploaded each frame. The mesh is rebuilt as a whole when the map changes,
+/// there are no partial updates for edits to the mesh itself.
+pub struct MapRenderPlugin;
+
+impl Plugin for MapRenderPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            VORONOI_SHADER_HANDLE,
+            "../assets/shaders/voronoi_flat.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<VoronoiMaterial>::default())
+            .init_resource::<CellColors>()
+            .configure_sets(Update, (MapRenderSet::Mesh, MapRenderSet::Upload).chain())
+            .add_systems(
+                Update,
+                spawn_map_mesh
+                    .in_set(MapRenderSet::Mesh)
+                    .run_if(resource_exists_and_changed::<VoronoiMap>),
+            );
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<CellColorWrites>()
+                .add_systems(ExtractSchedule, extract_cell_colors)
+                .add_systems(
+                    Render,
+                    write_cell_colors.in_set(RenderSet::PrepareResources),
+                );
+            // without a renderer nothing would take the queued writes
+            app.add_systems(
+                Update,
+                queue_cell_colors
+                    .in_set(MapRenderSet::Upload)
+                    .run_if(resource_changed::<CellColors>),
+            );
+        }
+    }
+}
+
+/// Systems that write [`CellColors`] should run between these sets.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapRenderSet {
+    /// (Re)builds the map mesh and resets the cell colours when the [`VoronoiMap`] changes.
+    Mesh,
+    /// Queues the changed cell colours for upload, colours set after this set are uploaded the next frame.
+    Upload,
+}
+
+/// Marks the entity holding the map mesh.
+#[derive(Component)]
+pub struct MapMesh;
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct VoronoiMaterial {
+    /// One linear rgba colour per cell.
+    #[storage(0, read_only)]
+    pub colors: Handle<ShaderStorageBuffer>,
+}
+
+impl Material for VoronoiMaterial {
+    fn vertex_shader() -> ShaderRef {
+        VORONOI_SHADER_HANDLE.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        VORONOI_SHADER_HANDLE.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.0.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            ATTRIBUTE_CELL.at_shader_location(1),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        // the map is flat, it should be visible from both sides
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+/// The colour of every cell of the [`VoronoiMap`], cells that are set are uploaded to the map material in [`MapRenderSet::Upload`].
+#[derive(Resource, Default)]
+pub struct CellColors {
+    colors: TrackedCellLayer<Color>,
+    buffer: Handle<ShaderStorageBuffer>,
+    /// Byte offsets and contents of the colour buffer waiting to be extracted.
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+impl CellColors {
+    pub fn get(&self, cell: CellId) -> Option<Color> {
+        self.colors.get(cell).copied()
+    }
+
+    pub fn set(&mut self, cell: CellId, color: impl Into<Color>) {
+        self.colors.set(cell, color.into());
+    }
+
+    pub fn layer(&self) -> &CellLayer<Color> {
+        self.colors.layer()
+    }
+
+    /// Colour every cell from a per-cell layer, e.g. to visualise elevation.
+    pub fn paint<T>(&mut self, layer: &CellLayer<T>, color: impl Fn(&T) -> Color) {
+        for (cell, value) in layer.iter() {
+            self.set(cell, color(value));
+        }
+    }
+
+    fn buffer_data(&self, cells: Range<usize>) -> Vec<u8> {
+        self.layer().values()[cells]
+            .iter()
+            .flat_map(|c| c.to_linear().to_f32_array())
+            .flat_map(f32::to_le_bytes)
+            .collect()
+    }
+}
+
+/// Byte ranges of the cell colour buffer waiting to be written, they are kept until the gpu buffer exists.
+#[derive(Resource, Default)]
+struct CellColorWrites {
+    buffer: AssetId<ShaderStorageBuffer>,
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+/// Build a bevy mesh from [`Voronoi::mesh_buffers`] with the [`ATTRIBUTE_CELL`] attribute.
+///
+/// Triangles are rotated so that the cell site is the first (provoking) vertex, which is the one used for flat interpolation.
+pub fn voronoi_mesh(voronoi: &Voronoi, asset_usage: RenderAssetUsages) -> Mesh {
+    mesh_from_buffers(voronoi.mesh_buffers(), asset_usage)
+}
+
+/// Like [`voronoi_mesh`], but the cells follow their [`NoisyEdges`].
+pub fn noisy_voronoi_mesh(
+    voronoi: &Voronoi,
+    edges: &NoisyEdges,
+    asset_usage: RenderAssetUsages,
+) -> Mesh {
+    mesh_from_buffers(edges.mesh_buffers(voronoi), asset_usage)
+}
+
+fn mesh_from_buffers(
+    (vertices, mut indices, ranges): (Vec<[f32; 3]>, Vec<u32>, CellLayer<CellMeshRange>),
+    asset_usage: RenderAssetUsages,
+) -> Mesh {
+    let mut cells = vec![0u32; vertices.len()];
+    for (cell, range) in ranges.iter() {
+        for triangle in indices[range.indices.clone()].chunks_exact_mut(3) {
+            triangle.rotate_right(1);
+        }
+        cells[range.vertices.start] = cell.0 as u32;
+    }
+    Mesh::new(PrimitiveTopology::TriangleList, asset_usage)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_attribute(ATTRIBUTE_CELL, cells)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+fn spawn_map_mesh(
+    mut commands: Commands,
+    map: Res<VoronoiMap>,
+    existing: Query<Entity, With<MapMesh>>,
+    mut colors: ResMut<CellColors>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mut materials: ResMut<Assets<VoronoiMaterial>>,
+) {
+    existing
+        .iter()
+        .for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let voronoi = map.voronoi();
+    colors.colors = TrackedCellLayer::new(CellLayer::new(voronoi, DEFAULT_CELL_COLOR));
+    colors.writes.clear();
+    let mut buffer = ShaderStorageBuffer::new(
+        &colors.buffer_data(0..voronoi.cell_count()),
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    // partial updates are written straight into the gpu buffer
+    buffer.buffer_description.usage |= BufferUsages::COPY_DST;
+    colors.buffer = buffers.add(buffer);
+
+    commands.spawn((
+        MapMesh,
+        Mesh3d(meshes.add(voronoi_mesh(voronoi, RenderAssetUsages::RENDER_WORLD))),
+        MeshMaterial3d(materials.add(VoronoiMaterial {
+            colors: colors.buffer.clone(),
+        })),
+        Transform::default(),
+    ));
+}
+
+/// Turn the dirty cell ranges into writes to the colour buffer.
+fn queue_cell_colors(mut colors: ResMut<CellColors>) {
+    if !colors.colors.is_dirty() {
+        return;
+    }
+    let ranges = colors.colors.take_dirty_ranges();
+    let stride = std::mem::size_of::<[f32; 4]>() as u64;
+    let writes: Vec<_> = ranges
+        .into_iter()
+        .map(|cells| (cells.start as u64 * stride, colors.buffer_data(cells)))
+        .collect();
+    colors.writes.extend(writes);
+}
+
+/// Move the queued writes out of the main world [`CellColors`].
+fn extract_cell_colors(mut main_world: ResMut<MainWorld>, mut pending: ResMut<CellColorWrites>) {
+    let Some(mut colors) = main_world.get_resource_mut::<CellColors>() else {
+        return;
+    };
+    // taking the writes is not a change to the colours
+    let colors = colors.bypass_change_detection();
+    if colors.buffer.id() != pending.buffer {
+        // writes for a previous map are stale, the new buffer is created with the current colours
+        pending.buffer = colors.buffer.id();
+        pending.writes.clear();
+    }
+    pending.writes.append(&mut colors.writes);
+}
+
+fn write_cell_colors(
+    mut pending: ResMut<CellColorWrites>,
+    buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    queue: Res<RenderQueue>,
+) {
+    let Some(gpu_buffer) = buffers.get(pending.buffer) else {
+        return;
+    };
+    pending
+        .writes
+        .drain(..)
+        .for_each(|(offset, data)| queue.write_buffer(&gpu_buffer.buffer, offset, &data));
+}
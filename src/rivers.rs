@@ -0,0 +1,167 @@
+use crate::{
+    layer::{CellLayer, CornerLayer},
+    terrain::Elevation,
+    voronoi::{CellId, CornerId, Voronoi},
+    water::Surface,
+};
+
+/// A river as a path of corners running downhill, ending at the coast, a lake or a sink.
+#[derive(Clone, Debug, Default)]
+pub struct River {
+    pub corners: Vec<CornerId>,
+    /// The flow along each edge of the path, `flow[i]` is the flow from `corners[i]` to `corners[i + 1]`.
+    pub flow: Vec<f32>,
+}
+
+impl River {
+    /// The edges of the path as pairs of corners in downhill order.
+    pub fn edges(&self) -> impl Iterator<Item = (CornerId, CornerId)> + '_ {
+        self.corners.windows(2).map(|edge| (edge[0], edge[1]))
+    }
+
+    /// The corner positions with a width for each, `width` maps the flow to a width.
+    ///
+    /// Each point uses the flow of the edge leaving it, the mouth uses the flow of the last edge.
+    pub fn polyline(&self, voronoi: &Voronoi, width: impl Fn(f32) -> f32) -> Vec<([f32; 2], f32)> {
+        self.corners
+            .iter()
+            .enumerate()
+            .map(|(i, corner)| {
+                let flow = self.flow[i.min(self.flow.len().saturating_sub(1))];
+                (voronoi.corner_position(*corner), width(flow))
+            })
+            .collect()
+    }
+}
+
+/// The drainage of the land towards the water and the rivers it forms.
+#[derive(Clone, Debug, Default)]
+pub struct Rivers {
+    /// The lowest neighbouring corner that is lower than each land corner, `None` for water corners and sinks.
+    pub downslope: CornerLayer<Option<CornerId>>,
+    /// The rainfall collected by each corner and every corner upstream of it.
+    pub flow: CornerLayer<f32>,
+    /// Every edge with at least the minimum flow belongs to exactly one river, tributaries end where they join.
+    pub rivers: Vec<River>,
+    /// Cells that have a river running along one of their edges.
+    pub riverside: CellLayer<bool>,
+}
+
+impl Rivers {
+    /// Let `rainfall` drain downhill over the corners, edges carrying at least `min_flow` become rivers.
+    ///
+    /// Corners touching a water cell collect no rain and end the rivers flowing into them.
+    pub fn generate(
+        voronoi: &Voronoi,
+        elevation: &Elevation,
+        surface: &CellLayer<Surface>,
+        rainfall: &CornerLayer<f32>,
+        min_flow: f32,
+    ) -> Self {
+        let corner_cells = voronoi.corner_cells();
+        let water = corner_cells.map(|cells| cells.iter().any(|cell| surface[*cell].is_water()));
+        let neighbors = voronoi.corner_neighbors();
+
+        let downslope = CornerLayer::from_fn(voronoi, |corner| {
+            if water[corner] {
+                return None;
+            }
+            neighbors[corner]
+                .iter()
+                .copied()
+                .filter(|n| elevation.corners[*n] < elevation.corners[corner])
+                .min_by(|a, b| elevation.corners[*a].total_cmp(&elevation.corners[*b]))
+        });
+
+        // accumulate from the highest corner down, so every corner is complete before it is passed on
+        let mut order: Vec<CornerId> = voronoi.corner_ids().collect();
+        order.sort_by(|a, b| elevation.corners[*b].total_cmp(&elevation.corners[*a]));
+        let mut flow =
+            CornerLayer::from_fn(
+                voronoi,
+                |corner: CornerId| {
+                    if water[corner] {
+                        0.0
+                    } else {
+                        rainfall[corner]
+                    }
+                },
+            );
+        for corner in order {
+            if let Some(down) = downslope[corner] {
+                flow[down] += flow[corner];
+            }
+        }
+
+        let is_river = |corner: CornerId| downslope[corner].is_some() && flow[corner] >= min_flow;
+        // the tributary with the most flow continues as the same river through a confluence
+        let mut main_upstream: CornerLayer<Option<CornerId>> = CornerLayer::new(voronoi, None);
+        for corner in voronoi.corner_ids().filter(|c| is_river(*c)) {
+            let Some(down) = downslope[corner] else {
+                continue;
+            };
+            match main_upstream[down] {
+                Some(main) if flow[main] >= flow[corner] => {}
+                _ => main_upstream[down] = Some(corner),
+            }
+        }
+
+        let mut rivers = Vec::new();
+        let mut riverside = CellLayer::new(voronoi, false);
+        for source in voronoi
+            .corner_ids()
+            .filter(|c| is_river(*c) && main_upstream[*c].is_none())
+        {
+            let mut river = River {
+                corners: vec![source],
+                flow: Vec::new(),
+            };
+            let mut corner = source;
+            while let Some(down) = downslope[corner] {
+                river.corners.push(down);
+                river.flow.push(flow[corner]);
+                corner_cells[corner]
+                    .iter()
+                    .filter(|cell| corner_cells[down].contains(cell))
+                    .for_each(|cell| riverside[*cell] = true);
+                if !is_river(down) || main_upstream[down] != Some(corner) {
+                    break;
+                }
+                corner = down;
+            }
+            rivers.push(river);
+        }
+
+        Self {
+            downslope,
+            flow,
+            rivers,
+            riverside,
+        }
+    }
+
+    pub fn is_riverside(&self, cell: CellId) -> bool {
+        self.riverside.get(cell).copied().unwrap_or(false)
+    }
+
+    /// The rivers as polylines whose width grows with the square root of the flow, up to `max_width`.
+    pub fn polylines(&self, voronoi: &Voronoi, max_width: f32) -> Vec<Vec<([f32; 2], f32)>> {
+        let max_flow = self
+            .rivers
+            .iter()
+            .flat_map(|river| river.flow.iter().copied())
+            .fold(0.0, f32::max);
+        self.rivers
+            .iter()
+            .map(|river| {
+                river.polyline(voronoi, |flow| {
+                    if max_flow > 0.0 {
+                        max_width * (flow / max_flow).sqrt()
+                    } else {
+                        0.0
+                    }
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::{
+    layer::CellLayer,
+    pathfinding::dijkstra,
+    settlements::{SettlementId, SettlementKind, Settlements},
+    voronoi::{CellId, Voronoi},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RoadClass {
+    /// Connects the larger settlements.
+    Highway,
+    /// Connects the smaller settlements to the nearest road.
+    Trail,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoadSettings {
+    /// Settlements of this kind or larger are joined by highways, the smaller ones by trails.
+    pub highway: SettlementKind,
+    /// The cost multiplier for following an existing road, below 1 to merge roads into a network.
+    pub reuse: f32,
+}
+
+impl Default for RoadSettings {
+    fn default() -> Self {
+        Self {
+            highway: SettlementKind::Town,
+            reuse: 0.5,
+        }
+    }
+}
+
+/// The stretch of road built to connect a settlement.
+#[derive(Clone, Debug)]
+pub struct Road {
+    pub settlement: SettlementId,
+    pub class: RoadClass,
+    /// The cells from the settlement to where the road joins the network, or another settlement.
+    pub cells: Vec<CellId>,
+}
+
+/// Roads between settlements along the edges between neighbouring cells.
+#[derive(Clone, Debug, Default)]
+pub struct Roads {
+    /// The class of every road edge, keyed by the pair of cells with the lower id first.
+    pub edges: HashMap<(CellId, CellId), RoadClass>,
+    pub roads: Vec<Road>,
+}
+
+impl Roads {
+    /// Connect every settlement to the nearest settlement or road of its class by its least-cost path.
+    ///
+    /// The larger settlements are joined first, so their highways form the backbone that the trails join. A settlement
+    /// that cannot reach any other, e.g. on an island, starts a network of its own. `cost` is the cost of moving between
+    /// two neighbouring cells, `None` if the move is impossible, e.g. [`TerrainCost::cost`](crate::cost::TerrainCost::cost).
+    pub fn generate(
+        voronoi: &Voronoi,
+        settlements: &Settlements,
+        settings: &RoadSettings,
+        cost: impl Fn(CellId, CellId) -> Option<f32>,
+    ) -> Self {
+        let mut roads = Roads::default();
+        // the cells a new road of each class may end at
+        let mut highway_network = CellLayer::new(voronoi, false);
+        let mut network = CellLayer::new(voronoi, false);
+        let mut ordered = settlements.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|(id, settlement)| (settlement.kind, *id));
+        for (id, settlement) in ordered {
+            let class = if settlement.kind <= settings.highway {
+                RoadClass::Highway
+            } else {
+                RoadClass::Trail
+            };
+            let targets = match class {
+                RoadClass::Highway => &highway_network,
+                RoadClass::Trail => &network,
+            };
+            let path = dijkstra(
+                voronoi,
+                settlement.cell,
+                |cell| targets[cell],
+                |a, b| {
+                    let step = cost(a, b)?;
+                    Some(match roads.class(a, b) {
+                        Some(_) => step * settings.reuse,
+                        None => step,
+                    })
+                },
+            );
+            let cells = path.map_or_else(|| vec![settlement.cell], |path| path.cells);
+            for cell in &cells {
+                network[*cell] = true;
+                if class == RoadClass::Highway {
+                    highway_network[*cell] = true;
+                }
+            }
+            for pair in cells.windows(2) {
+                let key = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                let edge = roads.edges.entry(key).or_insert(class);
+                if class == RoadClass::Highway {
+                    *edge = RoadClass::Highway;
+                }
+            }
+            if cells.len() > 1 {
+                roads.roads.push(Road {
+                    settlement: id,
+                    class,
+                    cells,
+                });
+            }
+        }
+        roads
+    }
+
+    /// The class of the road between the neighbouring cells `a` and `b`.
+    pub fn class(&self, a: CellId, b: CellId) -> Option<RoadClass> {
+        self.edges.get(&(a.min(b), a.max(b))).copied()
+    }
+
+    /// Every road edge as a segment between two sites, sorted by cells.
+    pub fn segments(&self, voronoi: &Voronoi) -> Vec<(RoadClass, [[f32; 2]; 2])> {
+        let mut edges = self.edges.iter().collect::<Vec<_>>();
+        edges.sort_unstable_by_key(|(key, _)| **key);
+        edges
+            .into_iter()
+            .map(|((a, b), class)| {
+                (
+                    *class,
+                    [voronoi.cell_position(*a), voronoi.cell_position(*b)],
+                )
+            })
+            .collect()
+    }
+
+    /// Every road as a polyline through the sites of its cells.
+    pub fn polylines(&self, voronoi: &Voronoi) -> Vec<(RoadClass, Vec<[f32; 2]>)> {
+        self.roads
+            .iter()
+            .map(|road| {
+                (
+                    road.class,
+                    road.cells
+                        .iter()
+                        .map(|cell| voronoi.cell_position(*cell))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
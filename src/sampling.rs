@@ -0,0 +1,192 @@
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin, RidgedMulti};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    layer::{CellLayer, CornerLayer},
+    voronoi::{CellId, Voronoi},
+};
+
+/// Where a cell samples a [`NoiseField`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellSample {
+    #[default]
+    Site,
+    /// The centroid of the cell polygon, see [`Voronoi::cell_centroid`].
+    Centroid,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fractal {
+    #[default]
+    Fbm,
+    /// Sharp crests where the noise crosses zero, for mountain ridges and canyons.
+    Ridged,
+}
+
+/// Offsets sample positions by a second noise field before sampling, bending the features of the noise.
+#[derive(Clone, Copy, Debug)]
+pub struct Warp {
+    /// The largest offset as a fraction of the map size.
+    pub amplitude: f64,
+    /// Roughly the number of warp features across the map.
+    pub frequency: f64,
+    pub octaves: usize,
+}
+
+impl Default for Warp {
+    fn default() -> Self {
+        Self {
+            amplitude: 0.05,
+            frequency: 2.0,
+            octaves: 3,
+        }
+    }
+}
+
+impl Warp {
+    /// The offset as a function of normalized positions, both components are in `[-amplitude, amplitude]`.
+    pub fn sampler(&self, seed: u32) -> impl Fn([f64; 2]) -> [f64; 2] {
+        let fbm = |seed| {
+            Fbm::<Perlin>::new(seed)
+                .set_frequency(self.frequency)
+                .set_octaves(self.octaves)
+        };
+        let (warp_x, warp_y) = (fbm(seed), fbm(seed.wrapping_add(1)));
+        let amplitude = self.amplitude;
+        move |p| {
+            [
+                amplitude * warp_x.get(p).clamp(-1.0, 1.0),
+                amplitude * warp_y.get(p).clamp(-1.0, 1.0),
+            ]
+        }
+    }
+}
+
+/// Fractal noise seeded from the map seed that is sampled at cells or corners into layers, values are in `[0, 1]`.
+///
+/// Positions are scaled by the boundary size, so `frequency` is roughly the number of features across the map.
+#[derive(Clone, Debug)]
+pub struct NoiseField {
+    pub fractal: Fractal,
+    pub frequency: f64,
+    pub octaves: usize,
+    /// The frequency multiplier from one octave to the next.
+    pub lacunarity: f64,
+    /// The amplitude multiplier from one octave to the next.
+    pub persistence: f64,
+    pub warp: Option<Warp>,
+    /// Mixed into the map seed, so fields on the same map differ.
+    pub salt: u32,
+}
+
+impl Default for NoiseField {
+    fn default() -> Self {
+        Self {
+            fractal: Fractal::Fbm,
+            frequency: 4.0,
+            octaves: 6,
+            lacunarity: Fbm::<Perlin>::DEFAULT_LACUNARITY,
+            persistence: Fbm::<Perlin>::DEFAULT_PERSISTENCE,
+            warp: None,
+            salt: 0,
+        }
+    }
+}
+
+impl NoiseField {
+    pub fn fbm(frequency: f64, octaves: usize) -> Self {
+        Self {
+            frequency,
+            octaves,
+            ..Default::default()
+        }
+    }
+
+    pub fn ridged(frequency: f64, octaves: usize) -> Self {
+        Self {
+            fractal: Fractal::Ridged,
+            frequency,
+            octaves,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_warp(mut self, warp: Warp) -> Self {
+        self.warp = Some(warp);
+        self
+    }
+
+    pub fn with_salt(mut self, salt: u32) -> Self {
+        self.salt = salt;
+        self
+    }
+
+    /// The noise as a function of map positions for `voronoi`.
+    pub fn sampler(&self, voronoi: &Voronoi) -> impl Fn([f32; 2]) -> f32 {
+        let seed = derive_seed(voronoi.seed(), self.salt);
+        let noise: Box<dyn NoiseFn<f64, 2> + Send + Sync> = match self.fractal {
+            Fractal::Fbm => Box::new(
+                Fbm::<Perlin>::new(seed)
+                    .set_frequency(self.frequency)
+                    .set_octaves(self.octaves)
+                    .set_lacunarity(self.lacunarity)
+                    .set_persistence(self.persistence),
+            ),
+            Fractal::Ridged => Box::new(
+                RidgedMulti::<Perlin>::new(seed)
+                    .set_frequency(self.frequency)
+                    .set_octaves(self.octaves)
+                    .set_lacunarity(self.lacunarity)
+                    .set_persistence(self.persistence),
+            ),
+        };
+        let warp = self.warp.map(|warp| warp.sampler(seed.wrapping_add(1)));
+        let bbox = voronoi.inner().bounding_box();
+        let scale = bbox.width().max(bbox.height());
+        move |[x, y]| {
+            let mut p = [x as f64 / scale, y as f64 / scale];
+            if let Some(warp) = &warp {
+                let [dx, dy] = warp(p);
+                p = [p[0] + dx, p[1] + dy];
+            }
+            ((noise.get(p) as f32 + 1.0) / 2.0).clamp(0.0, 1.0)
+        }
+    }
+
+    pub fn sample_cells(&self, voronoi: &Voronoi, at: CellSample) -> CellLayer<f32> {
+        let mut layer = CellLayer::new(voronoi, 0.0);
+        self.sample_cells_into(voronoi, at, &mut layer);
+        layer
+    }
+
+    pub fn sample_corners(&self, voronoi: &Voronoi) -> CornerLayer<f32> {
+        let mut layer = CornerLayer::new(voronoi, 0.0);
+        self.sample_corners_into(voronoi, &mut layer);
+        layer
+    }
+
+    /// Overwrite every cell of `layer` with the noise.
+    pub fn sample_cells_into(&self, voronoi: &Voronoi, at: CellSample, layer: &mut CellLayer<f32>) {
+        let sampler = self.sampler(voronoi);
+        let position = |cell: CellId| match at {
+            CellSample::Site => voronoi.cell_position(cell),
+            CellSample::Centroid => voronoi.cell_centroid(cell),
+        };
+        layer
+            .iter_mut()
+            .for_each(|(cell, value)| *value = sampler(position(cell)));
+    }
+
+    /// Overwrite every corner of `layer` with the noise.
+    pub fn sample_corners_into(&self, voronoi: &Voronoi, layer: &mut CornerLayer<f32>) {
+        let sampler = self.sampler(voronoi);
+        layer
+            .iter_mut()
+            .for_each(|(corner, value)| *value = sampler(voronoi.corner_position(corner)));
+    }
+}
+
+/// A seed for the noise functions derived from the map seed, different salts give unrelated seeds.
+pub fn derive_seed(seed: u64, salt: u32) -> u32 {
+    StdRng::seed_from_u64(seed ^ (salt as u64).rotate_left(32)).gen()
+}
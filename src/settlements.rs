@@ -0,0 +1,219 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    rivers::Rivers,
+    voronoi::{CellId, Voronoi},
+    water::Surface,
+};
+
+/// An index into [`Settlements::settlements`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct SettlementId(pub usize);
+
+/// The size of a settlement, from largest to smallest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SettlementKind {
+    City,
+    Town,
+    Village,
+}
+
+impl SettlementKind {
+    pub const ALL: [SettlementKind; 3] = [
+        SettlementKind::City,
+        SettlementKind::Town,
+        SettlementKind::Village,
+    ];
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settlement {
+    pub cell: CellId,
+    pub kind: SettlementKind,
+    /// The score of the cell when the settlement was placed, including the crowding penalty.
+    pub score: f32,
+}
+
+/// How many settlements of a kind to place and how close they may be to any other settlement.
+#[derive(Clone, Copy, Debug)]
+pub struct Quota {
+    pub count: usize,
+    /// The minimum distance as a fraction of the map size, the larger side of the boundary.
+    pub spacing: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct SettlementSettings {
+    /// The score for a cell next to the ocean.
+    pub coast: f32,
+    /// The score for a cell on a river or a lake shore.
+    pub river: f32,
+    /// The score per unit of fertility, averaged over the cell and its neighbours.
+    pub fertility: f32,
+    /// The most the score is lowered next to another settlement, fading out at `crowding_radius`.
+    pub crowding: f32,
+    /// A fraction of the map size, like the spacing of a [`Quota`].
+    pub crowding_radius: f32,
+    /// The most random score added to every cell, to break up ties on even terrain.
+    pub randomness: f32,
+    /// Mixed into the map seed for the random scores.
+    pub salt: u64,
+    pub cities: Quota,
+    pub towns: Quota,
+    pub villages: Quota,
+}
+
+impl SettlementSettings {
+    pub fn quota(&self, kind: SettlementKind) -> Quota {
+        match kind {
+            SettlementKind::City => self.cities,
+            SettlementKind::Town => self.towns,
+            SettlementKind::Village => self.villages,
+        }
+    }
+}
+
+impl Default for SettlementSettings {
+    fn default() -> Self {
+        Self {
+            coast: 1.0,
+            river: 1.5,
+            fertility: 2.0,
+            crowding: 1.0,
+            crowding_radius: 0.2,
+            randomness: 0.25,
+            salt: 0,
+            cities: Quota {
+                count: 4,
+                spacing: 0.15,
+            },
+            towns: Quota {
+                count: 12,
+                spacing: 0.07,
+            },
+            villages: Quota {
+                count: 40,
+                spacing: 0.03,
+            },
+        }
+    }
+}
+
+/// Cities, towns and villages placed on the best scoring land cells.
+#[derive(Clone, Debug, Default)]
+pub struct Settlements {
+    /// Cities first, then towns, then villages, each in the order they were placed.
+    pub settlements: Vec<Settlement>,
+    /// The score of every land cell before crowding, 0 for water.
+    pub score: CellLayer<f32>,
+    /// The settlement on every cell.
+    pub by_cell: CellLayer<Option<SettlementId>>,
+}
+
+impl Settlements {
+    /// Place settlements from the largest to the smallest kind, each on the free land cell with the highest score that
+    /// keeps the spacing of its kind to every settlement placed before.
+    ///
+    /// `fertility` is in `[0, 1]` per cell, e.g. `biomes.map(|biome| table.fertility(*biome))`. Fewer settlements are
+    /// placed if there is no room for more.
+    pub fn place(
+        voronoi: &Voronoi,
+        surface: &CellLayer<Surface>,
+        rivers: Option<&Rivers>,
+        fertility: &CellLayer<f32>,
+        settings: &SettlementSettings,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(voronoi.seed() ^ settings.salt);
+        let score = CellLayer::from_fn(voronoi, |cell| {
+            let jitter = rng.gen::<f32>() * settings.randomness;
+            if surface[cell].is_water() {
+                return 0.0;
+            }
+            let mut coast = false;
+            let mut fresh_water = rivers.is_some_and(|rivers| rivers.is_riverside(cell));
+            let mut farmland = fertility[cell];
+            let mut count = 1;
+            for neighbor in voronoi.cell_neighbors(cell) {
+                coast |= surface[neighbor] == Surface::Ocean;
+                fresh_water |= surface[neighbor] == Surface::Lake;
+                farmland += fertility[neighbor];
+                count += 1;
+            }
+            let flag = |value: bool, weight: f32| if value { weight } else { 0.0 };
+            flag(coast, settings.coast)
+                + flag(fresh_water, settings.river)
+                + settings.fertility * farmland / count as f32
+                + jitter
+        });
+
+        let bbox = voronoi.inner().bounding_box();
+        let size = bbox.width().max(bbox.height()) as f32;
+        let mut settlements: Vec<Settlement> = Vec::new();
+        let mut by_cell = CellLayer::new(voronoi, None);
+        let mut nearest = CellLayer::new(voronoi, f32::INFINITY);
+        let crowding = |nearest: f32| {
+            settings.crowding
+                * (1.0 - nearest / (settings.crowding_radius * size).max(f32::EPSILON)).max(0.0)
+        };
+        for kind in SettlementKind::ALL {
+            let quota = settings.quota(kind);
+            let spacing = quota.spacing * size;
+            for _ in 0..quota.count {
+                let best = voronoi
+                    .cell_ids()
+                    .filter(|cell| {
+                        !surface[*cell].is_water()
+                            && by_cell[*cell].is_none()
+                            && nearest[*cell] >= spacing
+                    })
+                    .map(|cell| (cell, score[cell] - crowding(nearest[cell])))
+                    // the lowest id wins ties
+                    .max_by(|(a, a_score), (b, b_score)| a_score.total_cmp(b_score).then(b.cmp(a)));
+                let Some((cell, cell_score)) = best else {
+                    break;
+                };
+                by_cell[cell] = Some(SettlementId(settlements.len()));
+                settlements.push(Settlement {
+                    cell,
+                    kind,
+                    score: cell_score,
+                });
+                let [x, y] = voronoi.cell_position(cell);
+                for (other, distance) in nearest.iter_mut() {
+                    let [ox, oy] = voronoi.cell_position(other);
+                    *distance = distance.min((ox - x).hypot(oy - y));
+                }
+            }
+        }
+
+        Self {
+            settlements,
+            score,
+            by_cell,
+        }
+    }
+
+    pub fn get(&self, id: SettlementId) -> &Settlement {
+        &self.settlements[id.0]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SettlementId, &Settlement)> {
+        self.settlements
+            .iter()
+            .enumerate()
+            .map(|(i, settlement)| (SettlementId(i), settlement))
+    }
+
+    pub fn of_kind(
+        &self,
+        kind: SettlementKind,
+    ) -> impl Iterator<Item = (SettlementId, &Settlement)> {
+        self.iter()
+            .filter(move |(_, settlement)| settlement.kind == kind)
+    }
+
+    pub fn at(&self, cell: CellId) -> Option<SettlementId> {
+        self.by_cell[cell]
+    }
+}
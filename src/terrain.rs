@@ -0,0 +1,134 @@
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+use crate::{
+    layer::{CellLayer, CornerLayer},
+    sampling::derive_seed,
+    voronoi::{CellId, Voronoi},
+};
+
+/// How elevation is assigned to cells, every method produces values in `[0, 1]`.
+#[derive(Clone, Debug)]
+pub enum ElevationMethod {
+    /// Highest at the center of the boundary and 0 at its edges, `exponent` shapes the falloff (1 is a cone).
+    Island { exponent: f32 },
+    /// fBm noise sampled at the cell sites. Positions are scaled by the boundary size, so `frequency` is roughly the number of features across the map.
+    ///
+    /// The noise is seeded from the map seed and `salt`, see [`derive_seed`].
+    Noise {
+        salt: u32,
+        frequency: f64,
+        octaves: usize,
+    },
+    /// Highest at `count` randomly chosen mountain cells and 0 at `radius` away from the nearest of them.
+    ///
+    /// The mountains are chosen from the map seed and `salt`, a radius of 0 only raises the mountain cells.
+    Mountains {
+        salt: u32,
+        count: usize,
+        radius: f32,
+    },
+    /// Weighted sum of methods.
+    Sum(Vec<(f32, ElevationMethod)>),
+    /// Product of methods, e.g. noise masked by an island falloff.
+    Product(Vec<ElevationMethod>),
+}
+
+impl ElevationMethod {
+    pub fn sample(&self, voronoi: &Voronoi) -> CellLayer<f32> {
+        let bbox = voronoi.inner().bounding_box();
+        let (center_x, center_y) = (bbox.center().x as f32, bbox.center().y as f32);
+        let (half_width, half_height) = (bbox.width() as f32 / 2.0, bbox.height() as f32 / 2.0);
+        match self {
+            ElevationMethod::Island { exponent } => CellLayer::from_fn(voronoi, |cell| {
+                let [x, y] = voronoi.cell_position(cell);
+                let distance = ((x - center_x) / half_width).hypot((y - center_y) / half_height);
+                (1.0 - distance).clamp(0.0, 1.0).powf(*exponent)
+            }),
+            ElevationMethod::Noise {
+                salt,
+                frequency,
+                octaves,
+            } => {
+                let fbm = Fbm::<Perlin>::new(derive_seed(voronoi.seed(), *salt))
+                    .set_frequency(*frequency)
+                    .set_octaves(*octaves);
+                let scale = 2.0 * half_width.max(half_height) as f64;
+                CellLayer::from_fn(voronoi, |cell| {
+                    let [x, y] = voronoi.cell_position(cell);
+                    let value = fbm.get([x as f64 / scale, y as f64 / scale]);
+                    ((value as f32 + 1.0) / 2.0).clamp(0.0, 1.0)
+                })
+            }
+            ElevationMethod::Mountains {
+                salt,
+                count,
+                radius,
+            } => {
+                let mut rng = StdRng::seed_from_u64(derive_seed(voronoi.seed(), *salt) as u64);
+                let radius = radius.max(f32::EPSILON);
+                let cell_count = voronoi.cell_count();
+                let peaks: Vec<[f32; 2]> = sample(&mut rng, cell_count, (*count).min(cell_count))
+                    .into_iter()
+                    .map(|i| voronoi.cell_position(CellId(i)))
+                    .collect();
+                CellLayer::from_fn(voronoi, |cell| {
+                    let [x, y] = voronoi.cell_position(cell);
+                    let nearest = peaks
+                        .iter()
+                        .map(|[px, py]| (x - px).hypot(y - py))
+                        .fold(f32::INFINITY, f32::min);
+                    (1.0 - nearest / radius).clamp(0.0, 1.0)
+                })
+            }
+            ElevationMethod::Sum(methods) => {
+                let mut sum = CellLayer::new(voronoi, 0.0);
+                for (weight, method) in methods {
+                    let layer = method.sample(voronoi);
+                    sum.values_mut()
+                        .iter_mut()
+                        .zip(layer.values())
+                        .for_each(|(s, v)| *s += weight * v);
+                }
+                sum.map(|v| v.clamp(0.0, 1.0))
+            }
+            ElevationMethod::Product(methods) => {
+                let mut product = CellLayer::new(voronoi, 1.0);
+                for method in methods {
+                    let layer = method.sample(voronoi);
+                    product
+                        .values_mut()
+                        .iter_mut()
+                        .zip(layer.values())
+                        .for_each(|(p, v)| *p *= v);
+                }
+                product
+            }
+        }
+    }
+}
+
+/// Elevation of every cell and corner in `[0, 1]`.
+#[derive(Clone, Debug, Default)]
+pub struct Elevation {
+    pub cells: CellLayer<f32>,
+    /// The mean elevation of the cells that meet at each corner.
+    pub corners: CornerLayer<f32>,
+}
+
+impl Elevation {
+    pub fn generate(voronoi: &Voronoi, method: &ElevationMethod) -> Self {
+        Self::from_cells(voronoi, method.sample(voronoi))
+    }
+
+    /// Use the given cell elevation and derive the corners from it.
+    pub fn from_cells(voronoi: &Voronoi, cells: CellLayer<f32>) -> Self {
+        let corners = voronoi.corner_cells().map(|touching| {
+            if touching.is_empty() {
+                return 0.0;
+            }
+            touching.iter().map(|cell| cells[*cell]).sum::<f32>() / touching.len() as f32
+        });
+        Self { cells, corners }
+    }
+}
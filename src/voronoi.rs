@@ -1,5 +1,17 @@
-use rand::Rng;
-use voronoice;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    layer::{CellLayer, CornerLayer},
+    sampling::{derive_seed, Warp},
+};
+
+/// Mixed into the map seed for the site warp of [`VoronoiBuilder::set_warp`].
+const SITE_WARP_SALT: u32 = 0x5173_3a7c;
 
 #[derive(Clone, Copy, Debug)]
 pub enum Boundary {
@@ -84,19 +96,51 @@ impl From<(f64, f64)> for Point {
     }
 }
 
-pub struct VoronoiBuilder {
-    inner: voronoice::VoronoiBuilder,
+/// Identifies a cell of a [`Voronoi`], cells share their index with the site they were built from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellId(pub usize);
+
+impl CellId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
 }
 
-impl Default for VoronoiBuilder {
-    fn default() -> Self {
-        Self {
-            inner: voronoice::VoronoiBuilder::default(),
-        }
+/// Identifies a corner of the voronoi cells, corners are shared by the cells that meet there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CornerId(pub usize);
+
+impl CornerId {
+    pub fn index(&self) -> usize {
+        self.0
     }
 }
 
+/// The location of a cell in the buffers returned by [`Voronoi::mesh_buffers`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CellMeshRange {
+    /// The indices of the cell's triangle fan, three per cell edge.
+    pub indices: Range<usize>,
+    /// The vertices added for this cell, starting with its site. Corners shared with an earlier cell are in that cell's range instead.
+    pub vertices: Range<usize>,
+}
+
+#[derive(Default)]
+pub struct VoronoiBuilder {
+    inner: voronoice::VoronoiBuilder,
+    seed: Option<u64>,
+    warp: Option<Warp>,
+}
+
 impl VoronoiBuilder {
+    /// The map seed, used for random sites and available to the generation stages as [`Voronoi::seed`].
+    ///
+    /// Set it before [`VoronoiBuilder::set_sites_random`], a random seed is picked if it is not set.
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
     pub fn set_boundary(mut self, boundary: Boundary) -> Self {
         self.inner = self.inner.set_bounding_box(boundary.into());
         self
@@ -111,11 +155,20 @@ impl VoronoiBuilder {
         let x_bounds = (boundary.top_left().0, boundary.bottom_right().0);
         // right handed coordinate system!
         let y_bounds = (boundary.bottom_right().1, boundary.top_left().1);
-        let points = VoronoiBuilder::random_points(count, x_bounds, y_bounds);
+        let seed = self.seed();
+        let points = VoronoiBuilder::random_points(seed, count, x_bounds, y_bounds);
         self = self.set_boundary(boundary);
         self.set_sites(points)
     }
 
+    /// Displace the sites by a noise field after relaxation, breaking up the regular look of relaxed cells.
+    ///
+    /// The offset is at most `warp.amplitude` times the map size and sites are kept inside the boundary.
+    pub fn set_warp(mut self, warp: Warp) -> Self {
+        self.warp = Some(warp);
+        self
+    }
+
     pub fn set_sites<T: Into<voronoice::Point>>(mut self, points: Vec<T>) -> Self {
         self.inner = self
             .inner
@@ -123,14 +176,90 @@ impl VoronoiBuilder {
         self
     }
 
+    /// Panics if the sites do not form a voronoi, see [`VoronoiBuilder::try_build`].
     pub fn build(self) -> Voronoi {
-        Voronoi {
-            voronoi: self.inner.build().unwrap(),
+        self.try_build()
+            .expect("the sites should form a voronoi, at least three of them not on a line")
+    }
+
+    /// Build the voronoi, `None` if the sites do not form one, e.g. because there are fewer than three.
+    pub fn try_build(mut self) -> Option<Voronoi> {
+        let seed = self.seed();
+        let mut inner = self.inner.build()?;
+        if let Some(warp) = self.warp {
+            inner = VoronoiBuilder::warp_sites(&inner, &warp, seed)?;
         }
+        let mut voronoi = Voronoi::from_inner(inner);
+        voronoi.seed = seed;
+        Some(voronoi)
+    }
+
+    /// Rebuild `voronoi` without relaxation from its warped sites.
+    fn warp_sites(
+        voronoi: &voronoice::Voronoi,
+        warp: &Warp,
+        seed: u64,
+    ) -> Option<voronoice::Voronoi> {
+        let bbox = voronoi.bounding_box().clone();
+        let scale = bbox.width().max(bbox.height());
+        let offset = warp.sampler(derive_seed(seed, SITE_WARP_SALT));
+        // stay clear of the boundary itself, sites on it are not inside
+        let margin = scale * 1e-6;
+        let (min_x, max_x) = (
+            bbox.center().x - bbox.width() / 2.0 + margin,
+            bbox.center().x + bbox.width() / 2.0 - margin,
+        );
+        let (min_y, max_y) = (
+            bbox.center().y - bbox.height() / 2.0 + margin,
+            bbox.center().y + bbox.height() / 2.0 - margin,
+        );
+        // sites pushed past the boundary are mirrored back inside, clamping would stack them on the boundary
+        let reflect = |v: f64, min: f64, max: f64| {
+            let v = if v < min { 2.0 * min - v } else { v };
+            let v = if v > max { 2.0 * max - v } else { v };
+            v.clamp(min, max)
+        };
+        let mut taken = HashSet::new();
+        let sites = voronoi
+            .sites()
+            .iter()
+            .map(|site| {
+                let [dx, dy] = offset([site.x / scale, site.y / scale]);
+                let warped = voronoice::Point {
+                    x: reflect(site.x + dx * scale, min_x, max_x),
+                    y: reflect(site.y + dy * scale, min_y, max_y),
+                };
+                // coincident sites break the triangulation, fall back to the original position and nudge it along x
+                // while that is taken as well
+                let mut candidate = warped;
+                let mut step = 0;
+                while !taken.insert((candidate.x.to_bits(), candidate.y.to_bits())) {
+                    candidate = voronoice::Point {
+                        x: reflect(site.x + step as f64 * margin, min_x, max_x),
+                        y: site.y,
+                    };
+                    step += 1;
+                }
+                candidate
+            })
+            .collect();
+        voronoice::VoronoiBuilder::default()
+            .set_bounding_box(bbox)
+            .set_sites(sites)
+            .build()
     }
 
-    fn random_points(count: usize, x_bounds: (f32, f32), y_bounds: (f32, f32)) -> Vec<Point> {
-        let mut rng = rand::thread_rng();
+    fn seed(&mut self) -> u64 {
+        *self.seed.get_or_insert_with(rand::random)
+    }
+
+    fn random_points(
+        seed: u64,
+        count: usize,
+        x_bounds: (f32, f32),
+        y_bounds: (f32, f32),
+    ) -> Vec<Point> {
+        let mut rng = StdRng::seed_from_u64(seed);
         (0..count)
             .map(|_| {
                 Point::new(
@@ -144,22 +273,31 @@ impl VoronoiBuilder {
 
 pub struct Voronoi {
     voronoi: voronoice::Voronoi,
+    // voronoice adds a separate vertex for every cell that is clipped against the boundary, this maps each vertex to
+    // the first vertex at the same position so that corners are shared by all the cells that meet there.
+    corners: Vec<usize>,
+    seed: u64,
 }
 
 impl Default for Voronoi {
     fn default() -> Self {
-        Self {
-            voronoi: voronoice::VoronoiBuilder::default()
+        Self::from_inner(
+            voronoice::VoronoiBuilder::default()
                 .set_bounding_box(Boundary::default().into())
                 .set_lloyd_relaxation_iterations(5)
                 .build()
                 .unwrap(),
-        }
+        )
     }
 }
 
 impl Voronoi {
-    pub fn random(boundary: Boundary, count: usize) {}
+    pub fn random(boundary: Boundary, count: usize) -> Self {
+        VoronoiBuilder::default()
+            .set_sites_random(boundary, count)
+            .build()
+    }
+
     pub fn new(points: Vec<(f32, f32)>, boundary: Boundary, relaxation: usize) -> Self {
         let voronoi = voronoice::VoronoiBuilder::default()
             .set_sites(
@@ -175,13 +313,226 @@ impl Voronoi {
             .set_lloyd_relaxation_iterations(relaxation)
             .build()
             .unwrap();
-        Self { voronoi: voronoi }
+        Self::from_inner(voronoi)
+    }
+
+    fn from_inner(voronoi: voronoice::Voronoi) -> Self {
+        let bbox = voronoi.bounding_box();
+        let epsilon = bbox.width().max(bbox.height()) * 1e-9;
+        let mut first_at: HashMap<(i64, i64), usize> = HashMap::new();
+        let corners = voronoi
+            .vertices()
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let key = (
+                    (v.x / epsilon).round() as i64,
+                    (v.y / epsilon).round() as i64,
+                );
+                *first_at.entry(key).or_insert(i)
+            })
+            .collect();
+        Self {
+            voronoi,
+            corners,
+            seed: 0,
+        }
+    }
+
+    /// The map seed set with [`VoronoiBuilder::set_seed`], 0 for voronois that were not built with a [`VoronoiBuilder`].
+    pub fn seed(&self) -> u64 {
+        self.seed
     }
 
     pub fn inner(&self) -> &voronoice::Voronoi {
         &self.voronoi
     }
 
+    pub fn cell_count(&self) -> usize {
+        self.voronoi.sites().len()
+    }
+
+    pub fn cell_ids(&self) -> impl Iterator<Item = CellId> {
+        (0..self.cell_count()).map(CellId)
+    }
+
+    pub fn corner_count(&self) -> usize {
+        self.voronoi.vertices().len()
+    }
+
+    pub fn corner_ids(&self) -> impl Iterator<Item = CornerId> {
+        (0..self.corner_count()).map(CornerId)
+    }
+
+    /// The position of the cell site.
+    pub fn cell_position(&self, cell: CellId) -> [f32; 2] {
+        let site = &self.voronoi.sites()[cell.0];
+        [site.x as f32, site.y as f32]
+    }
+
+    pub fn corner_position(&self, corner: CornerId) -> [f32; 2] {
+        let vertex = &self.voronoi.vertices()[corner.0];
+        [vertex.x as f32, vertex.y as f32]
+    }
+
+    /// The corners of the given cell in counter-clockwise order.
+    pub fn cell_corners(&self, cell: CellId) -> impl Iterator<Item = CornerId> + '_ {
+        self.voronoi.cells()[cell.0]
+            .iter()
+            .map(|v| CornerId(self.corners[*v]))
+    }
+
+    /// The corners connected to each corner by a cell edge.
+    pub fn corner_neighbors(&self) -> CornerLayer<Vec<CornerId>> {
+        let mut neighbors: CornerLayer<Vec<CornerId>> = CornerLayer::new(self, Vec::new());
+        for cell in self.cell_ids() {
+            let corners = self.cell_corners(cell).collect::<Vec<_>>();
+            ring(&corners).for_each(|(a, b)| {
+                if a != b && !neighbors[*a].contains(b) {
+                    neighbors[*a].push(*b);
+                    neighbors[*b].push(*a);
+                }
+            });
+        }
+        neighbors
+    }
+
+    /// The cells that share an edge with the given cell.
+    pub fn cell_neighbors(&self, cell: CellId) -> impl Iterator<Item = CellId> + '_ {
+        voronoice::NeighborSiteIterator::new(&self.voronoi, cell.0).map(CellId)
+    }
+
+    /// Whether the cell touches the boundary (or the hull if the diagram is not clipped).
+    pub fn is_boundary_cell(&self, cell: CellId) -> bool {
+        self.voronoi.cell(cell.0).is_on_hull()
+    }
+
+    /// The closed loops of corners that separate the cells where `inside` is true from the other cells and from the outside of the boundary.
+    ///
+    /// Loops run counter-clockwise around inside regions and clockwise around holes, the first corner is not repeated at the end.
+    pub fn boundary_loops(&self, inside: impl Fn(CellId) -> bool) -> Vec<Vec<CornerId>> {
+        self.labelled_boundary_loops(|cell| inside(cell).then_some(()))
+            .remove(&())
+            .unwrap_or_default()
+    }
+
+    /// Like [`Voronoi::boundary_loops`] for every group of cells with the same label at once, cells without a label are
+    /// outside of all groups.
+    pub fn labelled_boundary_loops<T: Ord + Clone>(
+        &self,
+        label: impl Fn(CellId) -> Option<T>,
+    ) -> BTreeMap<T, Vec<Vec<CornerId>>> {
+        let labels = self.cell_ids().map(label).collect::<Vec<_>>();
+        // every cell edge as a directed pair of corners, counter-clockwise around its cell
+        let mut edge_cells: HashMap<(CornerId, CornerId), CellId> = HashMap::new();
+        for cell in self.cell_ids() {
+            let corners = self.cell_corners(cell).collect::<Vec<_>>();
+            ring(&corners).for_each(|(a, b)| {
+                edge_cells.insert((*a, *b), cell);
+            });
+        }
+        // an edge is on the boundary if the cell on its other side (the reversed edge) has another label
+        let mut outgoing: BTreeMap<T, BTreeMap<CornerId, Vec<CornerId>>> = BTreeMap::new();
+        for cell in self.cell_ids() {
+            let Some(label) = &labels[cell.0] else {
+                continue;
+            };
+            let corners = self.cell_corners(cell).collect::<Vec<_>>();
+            let mut edges = ring(&corners)
+                .filter(|(a, b)| {
+                    edge_cells
+                        .get(&(**b, **a))
+                        .is_none_or(|n| labels[n.0].as_ref() != Some(label))
+                })
+                .peekable();
+            if edges.peek().is_none() {
+                continue;
+            }
+            let outgoing = outgoing.entry(label.clone()).or_default();
+            edges.for_each(|(a, b)| outgoing.entry(*a).or_default().push(*b));
+        }
+        outgoing
+            .into_iter()
+            .map(|(label, outgoing)| (label, trace_loops(outgoing)))
+            .collect()
+    }
+
+    /// The cells that meet at each corner. Some corners are not used by any cell (e.g. circumcenters that were clipped away).
+    pub fn corner_cells(&self) -> CornerLayer<Vec<CellId>> {
+        let mut corner_cells = CornerLayer::new(self, Vec::new());
+        for cell in self.cell_ids() {
+            for corner in self.cell_corners(cell) {
+                corner_cells[corner].push(cell);
+            }
+        }
+        corner_cells
+    }
+
+    /// The counter-clockwise polygon of the given cell.
+    pub fn cell_vertices(&self, cell: CellId) -> Vec<[f32; 2]> {
+        self.voronoi
+            .cell(cell.0)
+            .iter_vertices()
+            .map(|v| [v.x as f32, v.y as f32])
+            .collect()
+    }
+
+    /// The centre of mass of the cell polygon, which can be far from the site for stretched cells.
+    pub fn cell_centroid(&self, cell: CellId) -> [f32; 2] {
+        let vertices = self.cell_vertices(cell);
+        let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+        for ([ax, ay], [bx, by]) in ring(&vertices) {
+            let cross = ax * by - bx * ay;
+            area += cross;
+            x += (ax + bx) * cross;
+            y += (ay + by) * cross;
+        }
+        if area.abs() <= f32::EPSILON {
+            return self.cell_position(cell);
+        }
+        [x / (3.0 * area), y / (3.0 * area)]
+    }
+
+    /// Find the cell that contains `point`, or `None` if the point lies outside of the boundary.
+    pub fn cell_at<T: Into<Point>>(&self, point: T) -> Option<CellId> {
+        self.cell_at_near(point, CellId(0))
+    }
+
+    /// Same as [`Voronoi::cell_at`] but starts searching from `hint`, which is much faster when the point is known to be close to it (e.g. the previously picked cell).
+    pub fn cell_at_near<T: Into<Point>>(&self, point: T, hint: CellId) -> Option<CellId> {
+        let point: voronoice::Point = point.into().into();
+        if !self.voronoi.bounding_box().is_inside(&point) || self.cell_count() == 0 {
+            return None;
+        }
+        // a hint from another voronoi may be out of range
+        let hint = if hint.0 < self.cell_count() {
+            hint
+        } else {
+            CellId(0)
+        };
+        // the cell containing a point is the cell of the nearest site, walking greedily towards the point over the
+        // delaunay neighbours always ends up at the nearest site.
+        let sites = self.voronoi.sites();
+        let mut current = hint.0;
+        let mut current_distance = distance_squared(&sites[current], &point);
+        loop {
+            let closer = self
+                .voronoi
+                .cell(current)
+                .iter_neighbors()
+                .map(|n| (n, distance_squared(&sites[n], &point)))
+                .filter(|(_, d)| *d < current_distance)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match closer {
+                Some((n, d)) => {
+                    current = n;
+                    current_distance = d;
+                }
+                None => return Some(CellId(current)),
+            }
+        }
+    }
+
     pub fn bounding_box(&self) -> Vec<[f32; 2]> {
         let bbox = self.voronoi.bounding_box();
         let bbox_center = bbox.center();
@@ -210,17 +561,19 @@ impl Voronoi {
     /// Each cell is represented by a triangle fan with the final vertex of each triangle being the cell site (center), triangles are counter-clockwise order.
     /// You can use a flat vertex attribute with last triangle vertex to store values for each cell (just make sure to do this for all triangles in the cell!).
     /// The buffers are not optimized AT ALL, this might be a problem for large meshes, but its fine as a starting point. Cell triangles do however appear together in the buffers.
-    pub fn mesh_buffers(&self) -> (Vec<[f32; 3]>, Vec<u32>) {
+    /// The returned [`CellMeshRange`]s tell where each cell is in the buffers, so a single cell can be addressed without searching.
+    pub fn mesh_buffers(&self) -> (Vec<[f32; 3]>, Vec<u32>, CellLayer<CellMeshRange>) {
         let mut vertices = Vec::new();
         let mut indicies = Vec::new();
-        //let mut cells = Vec::new();
+        let mut cells = Vec::with_capacity(self.cell_count());
 
         // here we will store the index of the vertex in the new `verticies` buffer, so they can be reused.
         let mut index_map: Vec<Option<usize>> = vec![None; self.voronoi.vertices().len()];
         let old_verticies = self.voronoi.vertices();
         // assuming that the sites and cells are in the same order... it appears so!
 
-        self.voronoi.iter_cells().enumerate().for_each(|(i, cell)| {
+        self.voronoi.iter_cells().for_each(|cell| {
+            let indices_start = indicies.len();
             // add the verticies of the cell
             let center_index = vertices.len() as u32;
             let site = cell.site_position();
@@ -242,11 +595,41 @@ impl Voronoi {
                 indicies.push(index_map[*j].unwrap() as u32);
                 indicies.push(center_index); // add the center vertex
             });
+            cells.push(CellMeshRange {
+                indices: indices_start..indicies.len(),
+                vertices: center_index as usize..vertices.len(),
+            });
         });
-        (vertices, indicies)
+        (vertices, indicies, CellLayer::from_vec(cells))
     }
 }
 
+/// Follow the directed boundary edges from corner to corner until every edge is part of a loop.
+fn trace_loops(mut outgoing: BTreeMap<CornerId, Vec<CornerId>>) -> Vec<Vec<CornerId>> {
+    let mut loops = Vec::new();
+    while let Some(&start) = outgoing.keys().next() {
+        let mut corners = Vec::new();
+        let mut current = start;
+        while let Some(next) = outgoing.get_mut(&current).and_then(|next| next.pop()) {
+            // corners without edges left are dropped, so the next loop starts at the first key
+            if outgoing[&current].is_empty() {
+                outgoing.remove(&current);
+            }
+            corners.push(current);
+            current = next;
+            if current == start {
+                break;
+            }
+        }
+        loops.push(corners);
+    }
+    loops
+}
+
+fn distance_squared(a: &voronoice::Point, b: &voronoice::Point) -> f64 {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2)
+}
+
 fn ring<T>(vec: &[T]) -> impl Iterator<Item = (&T, &T)> {
     vec.iter().zip(vec.iter().cycle().skip(1)).take(vec.len())
 }
@@ -262,3 +645,70 @@ pub fn random_points(count: usize, bounds: (f32, f32)) -> Vec<(f32, f32)> {
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voronoi() -> Voronoi {
+        VoronoiBuilder::default()
+            .set_seed(1)
+            .set_sites_random(Boundary::CenteredSquare(100.0), 500)
+            .set_lloyd_relaxation_iterations(2)
+            .build()
+    }
+
+    fn signed_area(points: impl IntoIterator<Item = [f32; 2]>) -> f32 {
+        let points = points.into_iter().collect::<Vec<_>>();
+        ring(&points)
+            .map(|([ax, ay], [bx, by])| ax * by - bx * ay)
+            .sum::<f32>()
+            / 2.0
+    }
+
+    #[test]
+    fn boundary_loops_are_closed_and_oriented() {
+        let voronoi = voronoi();
+        let radius = |cell| {
+            let [x, y] = voronoi.cell_position(cell);
+            x.hypot(y)
+        };
+        let inside = |cell| (15.0..35.0).contains(&radius(cell));
+        let loops = voronoi.boundary_loops(inside);
+        assert_eq!(loops.len(), 2, "a ring has an outer loop and a hole");
+
+        let edges: HashSet<_> = voronoi
+            .cell_ids()
+            .flat_map(|cell| {
+                let corners = voronoi.cell_corners(cell).collect::<Vec<_>>();
+                ring(&corners).map(|(a, b)| (*a, *b)).collect::<Vec<_>>()
+            })
+            .collect();
+        for corners in &loops {
+            assert!(ring(corners).all(|(a, b)| edges.contains(&(*a, *b))));
+        }
+
+        let areas = loops
+            .iter()
+            .map(|corners| signed_area(corners.iter().map(|c| voronoi.corner_position(*c))))
+            .collect::<Vec<_>>();
+        assert_eq!(areas.iter().filter(|area| **area > 0.0).count(), 1);
+        assert_eq!(areas.iter().filter(|area| **area < 0.0).count(), 1);
+        let cells: f32 = voronoi
+            .cell_ids()
+            .filter(|cell| inside(*cell))
+            .map(|cell| signed_area(voronoi.cell_vertices(cell)))
+            .sum();
+        let total: f32 = areas.iter().sum();
+        assert!((total - cells).abs() < 1e-3 * cells, "{total} != {cells}");
+    }
+
+    #[test]
+    fn boundary_loop_of_all_cells_follows_the_boundary() {
+        let voronoi = voronoi();
+        let loops = voronoi.boundary_loops(|_| true);
+        assert_eq!(loops.len(), 1);
+        let area = signed_area(loops[0].iter().map(|c| voronoi.corner_position(*c)));
+        assert!((area - 100.0 * 100.0).abs() < 1.0, "{area}");
+    }
+}
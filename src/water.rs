@@ -0,0 +1,314 @@
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::{
+    layer::{CellLayer, ElementId, Layer},
+    pathfinding::MinHeapEntry,
+    terrain::Elevation,
+    voronoi::{CellId, CornerId, Voronoi},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Surface {
+    /// Water that is connected to the boundary of the map.
+    Ocean,
+    /// Water that is enclosed by land.
+    Lake,
+    Land,
+}
+
+impl Surface {
+    pub fn is_water(&self) -> bool {
+        !matches!(self, Surface::Land)
+    }
+}
+
+/// Classify cells from a water mask, water connected to a cell on the boundary is ocean and all other water is lake.
+pub fn classify(voronoi: &Voronoi, water: &CellLayer<bool>) -> CellLayer<Surface> {
+    let mut surface = water.map(|w| if *w { Surface::Lake } else { Surface::Land });
+    // flood the ocean in from the boundary
+    let mut queue: VecDeque<CellId> = voronoi
+        .cell_ids()
+        .filter(|cell| water[*cell] && voronoi.is_boundary_cell(*cell))
+        .collect();
+    queue
+        .iter()
+        .for_each(|cell| surface[*cell] = Surface::Ocean);
+    while let Some(cell) = queue.pop_front() {
+        for neighbor in voronoi.cell_neighbors(cell) {
+            if surface[neighbor] == Surface::Lake {
+                surface[neighbor] = Surface::Ocean;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    surface
+}
+
+/// Classify cells with an elevation below `sea_level` as water, see [`classify`].
+pub fn classify_elevation(
+    voronoi: &Voronoi,
+    elevation: &CellLayer<f32>,
+    sea_level: f32,
+) -> CellLayer<Surface> {
+    classify(voronoi, &elevation.map(|e| *e < sea_level))
+}
+
+/// The coastlines between land (including the lakes it encloses) and ocean as closed loops of corners.
+///
+/// Loops run counter-clockwise around the land, land that touches the boundary is closed along the boundary.
+pub fn coastlines(voronoi: &Voronoi, surface: &CellLayer<Surface>) -> Vec<Vec<CornerId>> {
+    voronoi.boundary_loops(|cell| surface[cell] != Surface::Ocean)
+}
+
+/// A basin of connected cells that water can only leave over its spill point.
+#[derive(Clone, Debug)]
+pub struct Depression {
+    pub cells: Vec<CellId>,
+    /// The lowest cell next to the depression, water that fills the depression overflows into it.
+    pub spill: CellId,
+    /// The elevation of the spill point, the level the depression fills up to.
+    pub level: f32,
+}
+
+impl Depression {
+    /// The difference between the level and the lowest cell of the depression.
+    pub fn depth(&self, elevation: &CellLayer<f32>) -> f32 {
+        let lowest = self
+            .cells
+            .iter()
+            .map(|cell| elevation[*cell])
+            .fold(f32::INFINITY, f32::min);
+        (self.level - lowest).max(0.0)
+    }
+}
+
+/// The depressions of the terrain found by a priority-flood from the ocean, see [`Depressions::find`].
+#[derive(Clone, Debug, Default)]
+pub struct Depressions {
+    /// The elevation with every depression filled, rising by at least `epsilon` away from the spill point so every cell drains to the ocean.
+    pub filled: CellLayer<f32>,
+    pub depressions: Vec<Depression>,
+    pub epsilon: f32,
+}
+
+impl Depressions {
+    /// Flood the terrain from the ocean (or the map edge if there is no ocean) in order of elevation.
+    pub fn find(
+        voronoi: &Voronoi,
+        elevation: &CellLayer<f32>,
+        surface: &CellLayer<Surface>,
+        epsilon: f32,
+    ) -> Self {
+        let mut filled = elevation.clone();
+        let mut outlets: Vec<CellId> = voronoi
+            .cell_ids()
+            .filter(|cell| surface[*cell] == Surface::Ocean)
+            .collect();
+        if outlets.is_empty() {
+            outlets = voronoi
+                .cell_ids()
+                .filter(|cell| voronoi.is_boundary_cell(*cell))
+                .collect();
+        }
+        priority_flood(
+            voronoi,
+            &mut filled,
+            outlets,
+            |cell| voronoi.cell_neighbors(cell).collect(),
+            epsilon,
+        );
+
+        // connected cells that were raised form one depression
+        let mut depressions = Vec::new();
+        let mut seen = CellLayer::new(voronoi, false);
+        let raised = |cell: CellId| filled[cell] > elevation[cell];
+        for start in voronoi.cell_ids() {
+            if seen[start] || !raised(start) {
+                continue;
+            }
+            seen[start] = true;
+            let mut cells = vec![start];
+            let mut spill: Option<CellId> = None;
+            let mut i = 0;
+            while let Some(&cell) = cells.get(i) {
+                i += 1;
+                for neighbor in voronoi.cell_neighbors(cell) {
+                    if raised(neighbor) {
+                        if !seen[neighbor] {
+                            seen[neighbor] = true;
+                            cells.push(neighbor);
+                        }
+                    } else if spill.is_none_or(|s| filled[neighbor] < filled[s]) {
+                        spill = Some(neighbor);
+                    }
+                }
+            }
+            // a depression covering the whole map has nowhere to spill
+            let Some(spill) = spill else {
+                continue;
+            };
+            depressions.push(Depression {
+                cells,
+                spill,
+                level: filled[spill],
+            });
+        }
+
+        Self {
+            filled,
+            depressions,
+            epsilon,
+        }
+    }
+
+    /// Turn depressions at least `min_lake_depth` deep into lakes at their level and fill the others.
+    ///
+    /// Lake cells are flattened to the level of the lake. The corner elevation is derived again from the cells and
+    /// flooded from the water corners as well (and the map edge if there is no ocean), so rivers following the corners
+    /// reach the water.
+    pub fn apply(
+        &self,
+        voronoi: &Voronoi,
+        elevation: &mut Elevation,
+        surface: &mut CellLayer<Surface>,
+        min_lake_depth: f32,
+    ) {
+        let mut cells = elevation.cells.clone();
+        for depression in &self.depressions {
+            if depression.depth(&elevation.cells) >= min_lake_depth {
+                for cell in &depression.cells {
+                    cells[*cell] = depression.level;
+                    if surface[*cell] == Surface::Land {
+                        surface[*cell] = Surface::Lake;
+                    }
+                }
+            } else {
+                for cell in &depression.cells {
+                    cells[*cell] = self.filled[*cell];
+                }
+            }
+        }
+        *elevation = Elevation::from_cells(voronoi, cells);
+
+        let corner_cells = voronoi.corner_cells();
+        let mut outlets = voronoi
+            .corner_ids()
+            .filter(|corner| corner_cells[*corner].iter().any(|c| surface[*c].is_water()))
+            .collect::<Vec<_>>();
+        if !surface.values().contains(&Surface::Ocean) {
+            // drain over the map edge like `find`, corners on the edge are shared by fewer than three cells
+            outlets.extend(voronoi.corner_ids().filter(|corner| {
+                let cells = &corner_cells[*corner];
+                cells.len() < 3 && cells.iter().any(|c| voronoi.is_boundary_cell(*c))
+            }));
+        }
+        let neighbors = voronoi.corner_neighbors();
+        priority_flood(
+            voronoi,
+            &mut elevation.corners,
+            outlets,
+            |corner| neighbors[corner].clone(),
+            self.epsilon,
+        );
+    }
+}
+
+/// Raise `levels` so that every element reachable from `outlets` has a path to one of them that descends by at least `epsilon` per step.
+fn priority_flood<I: ElementId + Ord>(
+    voronoi: &Voronoi,
+    levels: &mut Layer<I, f32>,
+    outlets: Vec<I>,
+    neighbors: impl Fn(I) -> Vec<I>,
+    epsilon: f32,
+) {
+    let mut done: Layer<I, bool> = Layer::new(voronoi, false);
+    let mut queue = BinaryHeap::new();
+    for id in outlets {
+        done[id] = true;
+        queue.push(MinHeapEntry {
+            cost: levels[id],
+            id,
+        });
+    }
+    // the lowest level is popped first
+    while let Some(MinHeapEntry { cost: level, id }) = queue.pop() {
+        for neighbor in neighbors(id) {
+            if done[neighbor] {
+                continue;
+            }
+            done[neighbor] = true;
+            levels[neighbor] = levels[neighbor].max(level + epsilon);
+            queue.push(MinHeapEntry {
+                cost: levels[neighbor],
+                id: neighbor,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layer::CornerLayer,
+        rivers::Rivers,
+        terrain::ElevationMethod,
+        voronoi::{Boundary, VoronoiBuilder},
+    };
+
+    /// The interior land corners without a lower neighbour, corners on the map edge drain over it.
+    fn sinks(voronoi: &Voronoi, elevation: &Elevation, surface: &CellLayer<Surface>) -> usize {
+        let rivers = Rivers::generate(
+            voronoi,
+            elevation,
+            surface,
+            &CornerLayer::new(voronoi, 1.0),
+            f32::INFINITY,
+        );
+        let corner_cells = voronoi.corner_cells();
+        voronoi
+            .corner_ids()
+            .filter(|corner| corner_cells[*corner].len() >= 3)
+            .filter(|corner| !corner_cells[*corner].iter().any(|c| surface[*c].is_water()))
+            .filter(|corner| rivers.downslope[*corner].is_none())
+            .count()
+    }
+
+    fn filled(sea_level: f32, min_lake_depth: f32) -> (Voronoi, Elevation, CellLayer<Surface>) {
+        let voronoi = VoronoiBuilder::default()
+            .set_seed(7)
+            .set_sites_random(Boundary::CenteredSquare(100.0), 2000)
+            .set_lloyd_relaxation_iterations(1)
+            .build();
+        let mut elevation = Elevation::generate(
+            &voronoi,
+            &ElevationMethod::Noise {
+                salt: 0,
+                frequency: 4.0,
+                octaves: 4,
+            },
+        );
+        let mut surface = classify_elevation(&voronoi, &elevation.cells, sea_level);
+        Depressions::find(&voronoi, &elevation.cells, &surface, 1e-4).apply(
+            &voronoi,
+            &mut elevation,
+            &mut surface,
+            min_lake_depth,
+        );
+        (voronoi, elevation, surface)
+    }
+
+    #[test]
+    fn priority_flood_leaves_no_interior_sinks() {
+        let (voronoi, elevation, surface) = filled(0.3, 0.01);
+        assert!(surface.values().contains(&Surface::Ocean));
+        assert_eq!(sinks(&voronoi, &elevation, &surface), 0);
+    }
+
+    #[test]
+    fn priority_flood_drains_over_the_edge_without_water() {
+        let (voronoi, elevation, surface) = filled(-10.0, f32::INFINITY);
+        assert!(!surface.values().iter().any(|s| s.is_water()));
+        assert_eq!(sinks(&voronoi, &elevation, &surface), 0);
+    }
+}
@@ -0,0 +1,567 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    layer::CellLayer,
+    voronoi::{CellId, Voronoi},
+};
+
+/// The tiles of a [`Wfc`] with their weights and the rules on which tiles may be neighbours.
+///
+/// All tiles may be neighbours unless forbidden, a tile may also require a number of neighbours of a tile.
+#[derive(Clone, Debug)]
+pub struct TileRules<T> {
+    tiles: Vec<T>,
+    weights: Vec<f32>,
+    /// `forbidden[a][b]` if tiles `a` and `b` may not be neighbours.
+    forbidden: Vec<Vec<bool>>,
+    requirements: Vec<Requirement>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Requirement {
+    tile: usize,
+    neighbor: usize,
+    count: usize,
+}
+
+impl<T> Default for TileRules<T> {
+    fn default() -> Self {
+        Self {
+            tiles: Vec::new(),
+            weights: Vec::new(),
+            forbidden: Vec::new(),
+            requirements: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + fmt::Debug> TileRules<T> {
+    /// Add a tile, it is picked with a probability proportional to `weight` when a cell is collapsed.
+    pub fn with_tile(mut self, tile: T, weight: f32) -> Self {
+        self.tiles.push(tile);
+        self.weights.push(weight.max(0.0));
+        self.forbidden.iter_mut().for_each(|row| row.push(false));
+        self.forbidden.push(vec![false; self.tiles.len()]);
+        self
+    }
+
+    /// Forbid `a` and `b` from being neighbours, e.g. swamp never touches desert. `a` and `b` may be the same tile.
+    pub fn with_forbidden(mut self, a: &T, b: &T) -> Self {
+        let (a, b) = (self.index(a), self.index(b));
+        self.forbidden[a][b] = true;
+        self.forbidden[b][a] = true;
+        self
+    }
+
+    /// Require every `tile` to have at least `count` neighbours that are `neighbor`, e.g. a pass needs two mountains.
+    pub fn with_required(mut self, tile: &T, neighbor: &T, count: usize) -> Self {
+        let requirement = Requirement {
+            tile: self.index(tile),
+            neighbor: self.index(neighbor),
+            count,
+        };
+        self.requirements.push(requirement);
+        self
+    }
+
+    pub fn tiles(&self) -> &[T] {
+        &self.tiles
+    }
+
+    fn index(&self, tile: &T) -> usize {
+        self.tiles
+            .iter()
+            .position(|t| t == tile)
+            .unwrap_or_else(|| {
+                panic!("{tile:?} must be added with `with_tile` before it is used in a rule")
+            })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WfcSettings {
+    /// Mixed into the map seed for the choices of cells and tiles.
+    pub salt: u64,
+    /// The search gives up after this many backtracks.
+    pub max_backtracks: usize,
+}
+
+impl Default for WfcSettings {
+    fn default() -> Self {
+        Self {
+            salt: 0,
+            max_backtracks: 10_000,
+        }
+    }
+}
+
+/// Why a tile was ruled out for a cell.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason<T> {
+    /// The initial constraint does not allow it.
+    Initial,
+    /// It is forbidden next to every tile left for `neighbor`.
+    Forbidden { neighbor: CellId },
+    /// It needs `count` neighbours that are `tile`, but only `available` neighbours can still be.
+    Required {
+        tile: T,
+        count: usize,
+        available: usize,
+    },
+    /// Every search that picked it failed.
+    Exhausted,
+}
+
+impl<T: fmt::Debug> fmt::Display for Reason<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Initial => write!(f, "ruled out by the initial constraint"),
+            Reason::Forbidden { neighbor } => {
+                write!(f, "forbidden next to every tile left for {neighbor:?}")
+            }
+            Reason::Required {
+                tile,
+                count,
+                available,
+            } => write!(
+                f,
+                "needs {count} {tile:?} neighbours but only {available} can be"
+            ),
+            Reason::Exhausted => write!(f, "every search that picked it failed"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WfcError<T> {
+    /// No tile is left for `cell` before any choice was made, or after every choice was backtracked.
+    Unsatisfiable {
+        cell: CellId,
+        /// Every tile with the last reason it was ruled out.
+        reasons: Vec<(T, Reason<T>)>,
+    },
+    /// The search gave up, `hardest` is the cell that ran out of tiles most often.
+    BacktrackLimit {
+        backtracks: usize,
+        hardest: CellId,
+        failures: usize,
+    },
+}
+
+impl<T: fmt::Debug> fmt::Display for WfcError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WfcError::Unsatisfiable { cell, reasons } => {
+                write!(f, "no tile fits {cell:?}")?;
+                for (tile, reason) in reasons {
+                    write!(f, "\n  {tile:?}: {reason}")?;
+                }
+                Ok(())
+            }
+            WfcError::BacktrackLimit {
+                backtracks,
+                hardest,
+                failures,
+            } => write!(
+                f,
+                "gave up after {backtracks} backtracks, {hardest:?} ran out of tiles {failures} times"
+            ),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for WfcError<T> {}
+
+/// Tiles assigned to every cell by wave function collapse, so that all rules hold.
+#[derive(Clone, Debug, Default)]
+pub struct Wfc<T> {
+    pub tiles: CellLayer<T>,
+    /// How often the search had to undo a choice.
+    pub backtracks: usize,
+}
+
+impl<T: Clone + PartialEq + fmt::Debug> Wfc<T> {
+    /// Repeatedly collapse the cell with the fewest tiles left to a random tile and rule out the tiles of the other cells
+    /// that no longer fit, undoing the latest choices when a cell runs out of tiles.
+    ///
+    /// `initial` restricts the tiles of a cell from the start, e.g. to keep water tiles on water. The choices are seeded
+    /// from the map seed.
+    pub fn solve(
+        voronoi: &Voronoi,
+        rules: &TileRules<T>,
+        settings: &WfcSettings,
+        initial: impl Fn(CellId, &T) -> bool,
+    ) -> Result<Self, WfcError<T>> {
+        let mut rng = StdRng::seed_from_u64(voronoi.seed() ^ settings.salt);
+        // cells with equally few tiles left are collapsed in a random order
+        let mut rank: Vec<usize> = (0..voronoi.cell_count()).collect();
+        rank.shuffle(&mut rng);
+        let mut solver = Solver::new(voronoi, rules, rank);
+
+        for cell in voronoi.cell_ids() {
+            for (tile, value) in rules.tiles.iter().enumerate() {
+                if !initial(cell, value) {
+                    solver.remove(cell, tile, Some(Reason::Initial));
+                }
+            }
+        }
+        let mut result = match solver.count.iter().position(|count| *count == 0) {
+            Some(cell) => Err(CellId(cell)),
+            None => solver.propagate(voronoi.cell_ids().collect()),
+        };
+
+        let mut decisions: Vec<Decision> = Vec::new();
+        let mut backtracks = 0;
+        let mut failures = CellLayer::new(voronoi, 0);
+        loop {
+            if let Err(failed) = result {
+                let Some(decision) = decisions.pop() else {
+                    return Err(solver.unsatisfiable(failed));
+                };
+                failures[failed] += 1;
+                backtracks += 1;
+                if backtracks > settings.max_backtracks {
+                    let (hardest, failures) = failures
+                        .iter()
+                        .max_by_key(|(cell, failures)| (**failures, Reverse(*cell)))
+                        .map(|(cell, failures)| (cell, *failures))
+                        .unwrap_or((CellId(0), 0));
+                    return Err(WfcError::BacktrackLimit {
+                        backtracks: backtracks - 1,
+                        hardest,
+                        failures,
+                    });
+                }
+                solver.undo(decision.mark);
+                result = match solver.remove(decision.cell, decision.tile, Some(Reason::Exhausted))
+                {
+                    true => solver.propagate(vec![decision.cell]),
+                    false => Err(decision.cell),
+                };
+                continue;
+            }
+
+            let Some(cell) = solver.next_open() else {
+                break;
+            };
+            let tile = solver.pick(&mut rng, cell);
+            decisions.push(Decision {
+                cell,
+                tile,
+                mark: solver.trail.len(),
+            });
+            for other in 0..rules.tiles.len() {
+                if other != tile {
+                    solver.remove(cell, other, None);
+                }
+            }
+            result = solver.propagate(vec![cell]);
+        }
+
+        Ok(Self {
+            tiles: CellLayer::from_fn(voronoi, |cell| {
+                let tile = (0..rules.tiles.len())
+                    .find(|tile| solver.possible(cell, *tile))
+                    .expect("every cell is collapsed to one tile");
+                rules.tiles[tile].clone()
+            }),
+            backtracks,
+        })
+    }
+}
+
+/// A choice of a tile for a cell, `mark` is the length of the trail before it.
+struct Decision {
+    cell: CellId,
+    tile: usize,
+    mark: usize,
+}
+
+/// The tiles left for every cell, with a trail of removals to backtrack.
+struct Solver<'a, T> {
+    rules: &'a TileRules<T>,
+    neighbors: CellLayer<Vec<CellId>>,
+    /// Whether tile `t` is left for cell `c`, at `c * tiles + t`.
+    possible: Vec<bool>,
+    /// The number of tiles left for every cell.
+    count: Vec<usize>,
+    /// The removed entries of `possible`, in order.
+    trail: Vec<usize>,
+    /// The last reason every entry of `possible` was removed.
+    reasons: Vec<Option<Reason<T>>>,
+    /// The random order of cells with equally many tiles left.
+    rank: Vec<usize>,
+    /// The cells by tiles left and rank, an entry is outdated once the count of its cell changed.
+    open: BinaryHeap<Reverse<(usize, usize, CellId)>>,
+}
+
+impl<'a, T: Clone + PartialEq + fmt::Debug> Solver<'a, T> {
+    fn new(voronoi: &Voronoi, rules: &'a TileRules<T>, rank: Vec<usize>) -> Self {
+        let entries = voronoi.cell_count() * rules.tiles.len();
+        let open = voronoi
+            .cell_ids()
+            .map(|cell| Reverse((rules.tiles.len(), rank[cell.0], cell)))
+            .collect();
+        Self {
+            rules,
+            neighbors: CellLayer::from_fn(voronoi, |cell| voronoi.cell_neighbors(cell).collect()),
+            possible: vec![true; entries],
+            count: vec![rules.tiles.len(); voronoi.cell_count()],
+            trail: Vec::new(),
+            reasons: vec![None; entries],
+            rank,
+            open,
+        }
+    }
+
+    /// The cell with the fewest tiles left that is not collapsed yet.
+    fn next_open(&mut self) -> Option<CellId> {
+        while let Some(Reverse((count, _, cell))) = self.open.pop() {
+            if count == self.count[cell.0] && count > 1 {
+                return Some(cell);
+            }
+        }
+        None
+    }
+
+    fn reopen(&mut self, cell: CellId) {
+        let count = self.count[cell.0];
+        if count > 1 {
+            self.open.push(Reverse((count, self.rank[cell.0], cell)));
+        }
+    }
+
+    fn possible(&self, cell: CellId, tile: usize) -> bool {
+        self.possible[cell.0 * self.rules.tiles.len() + tile]
+    }
+
+    /// Rule out `tile` for `cell`, false if no tile is left for the cell.
+    fn remove(&mut self, cell: CellId, tile: usize, reason: Option<Reason<T>>) -> bool {
+        let entry = cell.0 * self.rules.tiles.len() + tile;
+        if self.possible[entry] {
+            self.possible[entry] = false;
+            self.count[cell.0] -= 1;
+            self.trail.push(entry);
+            if reason.is_some() {
+                self.reasons[entry] = reason;
+            }
+            self.reopen(cell);
+        }
+        self.count[cell.0] > 0
+    }
+
+    /// Restore every removal after `mark`.
+    fn undo(&mut self, mark: usize) {
+        let tiles = self.rules.tiles.len();
+        for entry in self.trail.split_off(mark) {
+            self.possible[entry] = true;
+            self.reasons[entry] = None;
+            self.count[entry / tiles] += 1;
+            self.reopen(CellId(entry / tiles));
+        }
+    }
+
+    /// Rule out the tiles that no longer fit, starting from the neighbours of `changed`, the cell that ran out of tiles
+    /// on failure.
+    fn propagate(&mut self, mut changed: Vec<CellId>) -> Result<(), CellId> {
+        let mut queued = vec![false; self.count.len()];
+        changed.iter().for_each(|cell| queued[cell.0] = true);
+        while let Some(cell) = changed.pop() {
+            queued[cell.0] = false;
+            for i in 0..self.neighbors[cell].len() {
+                let neighbor = self.neighbors[cell][i];
+                let mut removed = false;
+                for tile in 0..self.rules.tiles.len() {
+                    if !self.possible(neighbor, tile) {
+                        continue;
+                    }
+                    if let Some(reason) = self.violation(neighbor, tile) {
+                        removed = true;
+                        if !self.remove(neighbor, tile, Some(reason)) {
+                            return Err(neighbor);
+                        }
+                    }
+                }
+                if removed && !queued[neighbor.0] {
+                    queued[neighbor.0] = true;
+                    changed.push(neighbor);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Why `tile` cannot be placed on `cell` with the tiles left for its neighbours, `None` if it can.
+    fn violation(&self, cell: CellId, tile: usize) -> Option<Reason<T>> {
+        let forbidden = &self.rules.forbidden[tile];
+        for neighbor in &self.neighbors[cell] {
+            let supported = (0..self.rules.tiles.len())
+                .any(|other| self.possible(*neighbor, other) && !forbidden[other]);
+            if !supported {
+                return Some(Reason::Forbidden {
+                    neighbor: *neighbor,
+                });
+            }
+        }
+        for requirement in self.rules.requirements.iter().filter(|r| r.tile == tile) {
+            let available = self.neighbors[cell]
+                .iter()
+                .filter(|neighbor| self.possible(**neighbor, requirement.neighbor))
+                .count();
+            if available < requirement.count {
+                return Some(Reason::Required {
+                    tile: self.rules.tiles[requirement.neighbor].clone(),
+                    count: requirement.count,
+                    available,
+                });
+            }
+        }
+        None
+    }
+
+    /// A random tile left for `cell`, by weight.
+    fn pick(&self, rng: &mut StdRng, cell: CellId) -> usize {
+        let left: Vec<usize> = (0..self.rules.tiles.len())
+            .filter(|tile| self.possible(cell, *tile))
+            .collect();
+        let total: f32 = left.iter().map(|tile| self.rules.weights[*tile]).sum();
+        if total <= 0.0 {
+            return left[rng.gen_range(0..left.len())];
+        }
+        let mut target = rng.gen_range(0.0..total);
+        for tile in &left {
+            target -= self.rules.weights[*tile];
+            if target < 0.0 {
+                return *tile;
+            }
+        }
+        left[left.len() - 1]
+    }
+
+    fn unsatisfiable(&self, cell: CellId) -> WfcError<T> {
+        let tiles = self.rules.tiles.len();
+        WfcError::Unsatisfiable {
+            cell,
+            reasons: (0..tiles)
+                .map(|tile| {
+                    let reason = self.reasons[cell.0 * tiles + tile]
+                        .clone()
+                        .unwrap_or(Reason::Exhausted);
+                    (self.rules.tiles[tile].clone(), reason)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voronoi::{Boundary, VoronoiBuilder};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Tile {
+        Grass,
+        Swamp,
+        Desert,
+        Mountain,
+        Pass,
+    }
+
+    fn voronoi() -> Voronoi {
+        VoronoiBuilder::default()
+            .set_seed(2)
+            .set_sites_random(Boundary::CenteredSquare(100.0), 1000)
+            .set_lloyd_relaxation_iterations(2)
+            .build()
+    }
+
+    #[test]
+    fn solution_keeps_every_rule() {
+        let voronoi = voronoi();
+        let rules = TileRules::default()
+            .with_tile(Tile::Grass, 3.0)
+            .with_tile(Tile::Swamp, 1.0)
+            .with_tile(Tile::Desert, 1.0)
+            .with_tile(Tile::Mountain, 1.0)
+            .with_tile(Tile::Pass, 0.5)
+            .with_forbidden(&Tile::Swamp, &Tile::Desert)
+            .with_forbidden(&Tile::Pass, &Tile::Pass)
+            .with_required(&Tile::Pass, &Tile::Mountain, 2);
+        let boundary = |cell: CellId| voronoi.is_boundary_cell(cell);
+        let wfc = Wfc::solve(&voronoi, &rules, &WfcSettings::default(), |cell, tile| {
+            !boundary(cell) || *tile == Tile::Mountain
+        })
+        .expect("the rules can be kept");
+
+        for cell in voronoi.cell_ids() {
+            let tile = wfc.tiles[cell];
+            let neighbors = voronoi
+                .cell_neighbors(cell)
+                .map(|n| wfc.tiles[n])
+                .collect::<Vec<_>>();
+            if boundary(cell) {
+                assert_eq!(tile, Tile::Mountain);
+            }
+            if tile == Tile::Swamp {
+                assert!(!neighbors.contains(&Tile::Desert));
+            }
+            if tile == Tile::Pass {
+                assert!(!neighbors.contains(&Tile::Pass));
+                assert!(neighbors.iter().filter(|n| **n == Tile::Mountain).count() >= 2);
+            }
+        }
+    }
+
+    #[test]
+    fn two_colouring_fails_cleanly() {
+        let voronoi = voronoi();
+        // every cell has neighbours that are also neighbours of each other, so two tiles cannot alternate
+        let rules = TileRules::default()
+            .with_tile(Tile::Grass, 1.0)
+            .with_tile(Tile::Desert, 1.0)
+            .with_forbidden(&Tile::Grass, &Tile::Grass)
+            .with_forbidden(&Tile::Desert, &Tile::Desert);
+        let settings = WfcSettings {
+            max_backtracks: 50,
+            ..Default::default()
+        };
+        match Wfc::solve(&voronoi, &rules, &settings, |_, _| true) {
+            Err(WfcError::Unsatisfiable { reasons, .. }) => {
+                let tiles = reasons.iter().map(|(tile, _)| *tile).collect::<Vec<_>>();
+                assert_eq!(tiles, [Tile::Grass, Tile::Desert]);
+            }
+            Err(WfcError::BacktrackLimit { backtracks, .. }) => assert_eq!(backtracks, 50),
+            Ok(_) => panic!("a triangulation cannot be coloured with two tiles"),
+        }
+    }
+
+    #[test]
+    fn conflicting_initial_tiles_are_unsatisfiable() {
+        let voronoi = voronoi();
+        let rules = TileRules::default()
+            .with_tile(Tile::Grass, 1.0)
+            .with_tile(Tile::Mountain, 1.0)
+            .with_forbidden(&Tile::Grass, &Tile::Mountain);
+        let cell = CellId(0);
+        let neighbor = voronoi.cell_neighbors(cell).next().unwrap();
+        let result = Wfc::solve(
+            &voronoi,
+            &rules,
+            &WfcSettings::default(),
+            |c, tile| match c {
+                c if c == cell => *tile == Tile::Grass,
+                c if c == neighbor => *tile == Tile::Mountain,
+                _ => true,
+            },
+        );
+        let Err(WfcError::Unsatisfiable { reasons, .. }) = result else {
+            panic!("expected the initial tiles to conflict, got {result:?}");
+        };
+        assert!(reasons
+            .iter()
+            .any(|(_, reason)| matches!(reason, Reason::Forbidden { .. })));
+    }
+}
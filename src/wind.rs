@@ -0,0 +1,161 @@
+use crate::{
+    layer::{CellLayer, CornerLayer},
+    terrain::Elevation,
+    voronoi::{CellId, Voronoi},
+    water::Surface,
+};
+
+#[derive(Clone, Debug)]
+pub struct WindSettings {
+    /// The direction the prevailing wind blows towards, it does not need to be normalized.
+    pub direction: [f32; 2],
+    /// The humidity of the air entering the map on the windward side, in `[0, 1]`.
+    pub initial_humidity: f32,
+    /// The humidity picked up over every water cell.
+    pub evaporation: f32,
+    /// The fraction of the humidity that rains down on every land cell.
+    pub rain_rate: f32,
+    /// The extra fraction that rains down per unit of elevation the air has to climb.
+    pub orographic_rate: f32,
+}
+
+impl Default for WindSettings {
+    fn default() -> Self {
+        Self {
+            direction: [1.0, 0.0],
+            initial_humidity: 0.5,
+            evaporation: 0.2,
+            rain_rate: 0.05,
+            orographic_rate: 5.0,
+        }
+    }
+}
+
+/// Moisture carried across the cells by the prevailing wind, raining on windward slopes and leaving a rain shadow behind them.
+#[derive(Clone, Debug, Default)]
+pub struct Wind {
+    /// The direction the air leaves each cell in, scaled by the humidity it carries.
+    pub vectors: CellLayer<[f32; 2]>,
+    /// The humidity of the air leaving each cell.
+    pub humidity: CellLayer<f32>,
+    /// The rain that fell on each cell.
+    pub rainfall: CellLayer<f32>,
+}
+
+impl Wind {
+    pub fn simulate(
+        voronoi: &Voronoi,
+        elevation: &Elevation,
+        surface: &CellLayer<Surface>,
+        settings: &WindSettings,
+    ) -> Self {
+        let [dx, dy] = settings.direction;
+        let length = dx.hypot(dy);
+        let direction = if length > 0.0 {
+            [dx / length, dy / length]
+        } else {
+            [1.0, 0.0]
+        };
+        let along = |cell: CellId| {
+            let [x, y] = voronoi.cell_position(cell);
+            x * direction[0] + y * direction[1]
+        };
+
+        // the share of a cell's air that moves on to each neighbour, by how well it lines up with the wind
+        let downwind = CellLayer::from_fn(voronoi, |cell| {
+            let [x, y] = voronoi.cell_position(cell);
+            let weighted: Vec<(CellId, f32)> = voronoi
+                .cell_neighbors(cell)
+                .filter_map(|neighbor| {
+                    let [nx, ny] = voronoi.cell_position(neighbor);
+                    let (ox, oy) = (nx - x, ny - y);
+                    let distance = ox.hypot(oy);
+                    let alignment = (ox * direction[0] + oy * direction[1]) / distance;
+                    (distance > 0.0 && alignment > 0.0).then_some((neighbor, alignment))
+                })
+                .collect();
+            let total: f32 = weighted.iter().map(|(_, w)| w).sum();
+            weighted
+                .into_iter()
+                .map(|(neighbor, w)| (neighbor, w / total))
+                .collect::<Vec<_>>()
+        });
+
+        let mut order: Vec<CellId> = voronoi.cell_ids().collect();
+        order.sort_by(|a, b| along(*a).total_cmp(&along(*b)));
+
+        let mut inflow = CellLayer::new(voronoi, Inflow::default());
+        let mut humidity = CellLayer::new(voronoi, 0.0);
+        let mut rainfall = CellLayer::new(voronoi, 0.0);
+        let mut vectors = CellLayer::new(voronoi, [0.0, 0.0]);
+        for cell in order {
+            let height = elevation.cells[cell];
+            let Inflow {
+                air,
+                weight,
+                land_height,
+                land_weight,
+            } = inflow[cell];
+            // cells without upwind neighbours receive air from outside the map
+            let mut air = if weight > 0.0 {
+                air / weight
+            } else {
+                settings.initial_humidity
+            };
+            if surface[cell].is_water() {
+                air = (air + settings.evaporation).min(1.0);
+            } else {
+                // only climbs from land count, the sea floor is not a slope
+                let climb = if land_weight > 0.0 {
+                    (height - land_height / land_weight).max(0.0)
+                } else {
+                    0.0
+                };
+                let rain = (air * (settings.rain_rate + settings.orographic_rate * climb)).min(air);
+                rainfall[cell] = rain;
+                air -= rain;
+            }
+            humidity[cell] = air;
+
+            let [x, y] = voronoi.cell_position(cell);
+            for (neighbor, share) in &downwind[cell] {
+                let next = &mut inflow[*neighbor];
+                next.air += air * share;
+                next.weight += share;
+                if !surface[cell].is_water() {
+                    next.land_height += height * share;
+                    next.land_weight += share;
+                }
+                let [nx, ny] = voronoi.cell_position(*neighbor);
+                let distance = (nx - x).hypot(ny - y);
+                vectors[cell][0] += air * share * (nx - x) / distance;
+                vectors[cell][1] += air * share * (ny - y) / distance;
+            }
+        }
+
+        Self {
+            vectors,
+            humidity,
+            rainfall,
+        }
+    }
+
+    /// The rain that fell around each corner, the mean of the cells that meet there, e.g. to feed [`Rivers`](crate::rivers::Rivers).
+    pub fn corner_rainfall(&self, voronoi: &Voronoi) -> CornerLayer<f32> {
+        voronoi.corner_cells().map(|cells| {
+            if cells.is_empty() {
+                return 0.0;
+            }
+            cells.iter().map(|cell| self.rainfall[*cell]).sum::<f32>() / cells.len() as f32
+        })
+    }
+}
+
+/// The air arriving at a cell from its upwind neighbours and the height of the land it came over, weighted by their shares.
+#[derive(Clone, Copy, Debug, Default)]
+struct Inflow {
+    air: f32,
+    weight: f32,
+    land_height: f32,
+    land_weight: f32,
+}